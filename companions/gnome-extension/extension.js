@@ -0,0 +1,58 @@
+// Tells the Chronos tracker which window is focused. Calls
+// org.chronos.Focus1.Update(title, app, pid) on the tracker's session bus
+// name whenever focus or the focused window's title changes, and again when
+// the tracker (re)appears on the bus.
+
+import Gio from 'gi://Gio';
+import GLib from 'gi://GLib';
+import {Extension} from 'resource:///org/gnome/shell/extensions/extension.js';
+
+const BUS_NAME = 'org.chronos.Tracker';
+const OBJECT_PATH = '/org/chronos/Tracker';
+const INTERFACE = 'org.chronos.Focus1';
+
+export default class ChronosFocusExtension extends Extension {
+    enable() {
+        this._window = null;
+        this._titleId = 0;
+        this._focusId = global.display.connect('notify::focus-window', () => this._follow());
+        this._watchId = Gio.bus_watch_name(
+            Gio.BusType.SESSION, BUS_NAME, Gio.BusNameWatcherFlags.NONE, () => this._publish(), null);
+        this._follow();
+    }
+
+    disable() {
+        global.display.disconnect(this._focusId);
+        Gio.bus_unwatch_name(this._watchId);
+        this._unfollowTitle();
+        this._window = null;
+    }
+
+    _follow() {
+        this._unfollowTitle();
+        this._window = global.display.focus_window;
+        if (this._window)
+            this._titleId = this._window.connect('notify::title', () => this._publish());
+        this._publish();
+    }
+
+    _unfollowTitle() {
+        if (this._window && this._titleId)
+            this._window.disconnect(this._titleId);
+        this._titleId = 0;
+    }
+
+    _publish() {
+        const window = this._window;
+        const pid = window ? window.get_pid() : 0;
+        // pid as text, like the KWin script, which can't choose D-Bus integer types
+        const args = new GLib.Variant('(sss)', [
+            window?.get_title() ?? '',
+            window?.get_wm_class() ?? '',
+            pid > 0 ? String(pid) : '',
+        ]);
+        // fails harmlessly while the tracker isn't running
+        Gio.DBus.session.call(BUS_NAME, OBJECT_PATH, INTERFACE, 'Update', args, null,
+            Gio.DBusCallFlags.NO_AUTO_START, -1, null, null);
+    }
+}
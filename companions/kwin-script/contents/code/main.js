@@ -0,0 +1,57 @@
+// Tells the Chronos tracker which window is focused. Calls
+// org.chronos.Focus1.Update(title, app, pid) on the tracker's session bus
+// name whenever focus or the focused window's title changes, and again when
+// the tracker (re)appears on the bus. KWin scripts can only call D-Bus
+// methods, and numbers arrive with whatever type Qt picks, so the pid is sent
+// as text.
+
+const BUS_NAME = "org.chronos.Tracker";
+const OBJECT_PATH = "/org/chronos/Tracker";
+const INTERFACE = "org.chronos.Focus1";
+// How often to look for the tracker on the bus
+const WATCH_INTERVAL_MS = 5000;
+
+let current = null;
+let trackerRunning = false;
+
+function publish() {
+    callDBus(BUS_NAME, OBJECT_PATH, INTERFACE, "Update",
+        current ? current.caption : "",
+        current ? current.resourceClass : "",
+        current && current.pid > 0 ? String(current.pid) : "");
+}
+
+function follow(window) {
+    if (current)
+        current.captionChanged.disconnect(publish);
+    current = window;
+    if (current)
+        current.captionChanged.connect(publish);
+    publish();
+}
+
+// Plasma 6 calls them windows, Plasma 5 clients
+if (workspace.windowActivated) {
+    workspace.windowActivated.connect(follow);
+    follow(workspace.activeWindow);
+} else {
+    workspace.clientActivated.connect(follow);
+    follow(workspace.activeClient);
+}
+
+// Scripts can't subscribe to NameOwnerChanged like the GNOME extension's
+// name watch does, so ask the bus instead
+function watchTracker() {
+    callDBus("org.freedesktop.DBus", "/org/freedesktop/DBus", "org.freedesktop.DBus",
+        "NameHasOwner", BUS_NAME, function (running) {
+            if (running && !trackerRunning)
+                publish();
+            trackerRunning = running;
+        });
+}
+
+const watch = new QTimer();
+watch.interval = WATCH_INTERVAL_MS;
+watch.timeout.connect(watchTracker);
+watch.start();
+watchTracker();
@@ -0,0 +1,501 @@
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use std::io::{self, Write};
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+// -------------------- sign-in --------------------
+// Loopback sign-in: we listen on a random 127.0.0.1 port, open the browser on
+// the dashboard's sign-in page with that port and a random state, and the
+// token page hands the token back to http://127.0.0.1:<port>/callback.
+//
+// Machines without a browser use the device flow (RFC 8628) instead: we show
+// a short code, the user approves it on any other device, and we poll the
+// token endpoint until it is approved. Pasting a token by hand is the last
+// resort.
+
+pub const DEFAULT_SERVER_URL: &str = "https://chronos-red-five.vercel.app";
+
+// How long to wait for the browser to come back with a token
+const LOOPBACK_TIMEOUT: Duration = Duration::from_secs(300);
+
+const DEVICE_GRANT_TYPE: &str = "urn:ietf:params:oauth:grant-type:device_code";
+// RFC 8628 section 3.5: add 5 seconds on every slow_down
+const SLOW_DOWN_STEP: Duration = Duration::from_secs(5);
+const MAX_POLL_INTERVAL: Duration = Duration::from_secs(60);
+
+const CALLBACK_PAGE: &str = "<!doctype html><html><head><meta charset=\"utf-8\"><title>Chronos</title></head>\
+<body style=\"font-family:sans-serif;text-align:center;margin-top:4em\">\
+<h1>&#x2705; Chronos is signed in</h1><p>You can close this tab and return to the app.</p></body></html>";
+
+fn random_state() -> String {
+    let mut buf = [0u8; 16];
+    if getrandom::getrandom(&mut buf).is_err() {
+        // Not security critical enough to give up on sign-in over
+        let nanos = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_nanos())
+            .unwrap_or(0);
+        buf.copy_from_slice(&(nanos ^ std::process::id() as u128).to_le_bytes());
+    }
+    buf.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+// Whether there is a desktop to open a browser on
+fn has_display() -> bool {
+    if cfg!(any(windows, target_os = "macos")) {
+        return true;
+    }
+    std::env::var_os("DISPLAY").is_some() || std::env::var_os("WAYLAND_DISPLAY").is_some()
+}
+
+pub fn open_browser(url: &str) -> bool {
+    if !has_display() {
+        return false;
+    }
+    #[cfg(windows)]
+    let result = std::process::Command::new("rundll32")
+        .args(["url.dll,FileProtocolHandler", url])
+        .spawn();
+    #[cfg(target_os = "macos")]
+    let result = std::process::Command::new("open").arg(url).spawn();
+    #[cfg(not(any(windows, target_os = "macos")))]
+    let result = std::process::Command::new("xdg-open").arg(url).spawn();
+    result.is_ok()
+}
+
+fn percent_decode(s: &str) -> String {
+    let bytes = s.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'+' => out.push(b' '),
+            b'%' if i + 2 < bytes.len() => {
+                let hex = std::str::from_utf8(&bytes[i + 1..i + 3]).ok();
+                match hex.and_then(|h| u8::from_str_radix(h, 16).ok()) {
+                    Some(b) => {
+                        out.push(b);
+                        i += 2;
+                    }
+                    None => out.push(b'%'),
+                }
+            }
+            b => out.push(b),
+        }
+        i += 1;
+    }
+    String::from_utf8_lossy(&out).to_string()
+}
+
+fn query_param(query: &str, name: &str) -> Option<String> {
+    query
+        .split('&')
+        .filter_map(|pair| pair.split_once('='))
+        .find(|(k, _)| *k == name)
+        .map(|(_, v)| percent_decode(v))
+}
+
+async fn respond(stream: &mut TcpStream, status: &str, body: &str) {
+    let response = format!(
+        "HTTP/1.1 {}\r\nContent-Type: text/html; charset=utf-8\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        body
+    );
+    let _ = stream.write_all(response.as_bytes()).await;
+    let _ = stream.shutdown().await;
+}
+
+// Read the request head and return the request target ("/callback?...")
+async fn read_request_target(stream: &mut TcpStream) -> Option<String> {
+    let mut buf = Vec::new();
+    let mut chunk = [0u8; 1024];
+    while !buf.windows(4).any(|w| w == b"\r\n\r\n") && buf.len() < 16 * 1024 {
+        let n = stream.read(&mut chunk).await.ok()?;
+        if n == 0 {
+            break;
+        }
+        buf.extend_from_slice(&chunk[..n]);
+    }
+    let head = String::from_utf8_lossy(&buf);
+    let mut request_line = head.lines().next()?.split_whitespace();
+    match (request_line.next(), request_line.next()) {
+        (Some("GET"), Some(target)) => Some(target.to_string()),
+        _ => None,
+    }
+}
+
+// Serve the loopback listener until the callback with our state arrives
+async fn wait_for_callback(listener: TcpListener, state: &str) -> Result<String, Box<dyn std::error::Error>> {
+    loop {
+        let (mut stream, _) = listener.accept().await?;
+        let target = match tokio::time::timeout(Duration::from_secs(10), read_request_target(&mut stream)).await {
+            Ok(Some(target)) => target,
+            _ => continue,
+        };
+        let (path, query) = target.split_once('?').unwrap_or((target.as_str(), ""));
+        if path != "/callback" {
+            respond(&mut stream, "404 Not Found", "Not found").await;
+            continue;
+        }
+        if query_param(query, "state").as_deref() != Some(state) {
+            respond(&mut stream, "400 Bad Request", "Sign-in state did not match, please try again.").await;
+            continue;
+        }
+        match query_param(query, "token").filter(|t| !t.trim().is_empty()) {
+            Some(token) => {
+                respond(&mut stream, "200 OK", CALLBACK_PAGE).await;
+                return Ok(token.trim().to_string());
+            }
+            None => respond(&mut stream, "400 Bad Request", "No token in callback.").await,
+        }
+    }
+}
+
+// Sign in through the browser and receive the token on a loopback port
+pub async fn login_with_loopback(server_url: &str, wait: Duration) -> Result<String, Box<dyn std::error::Error>> {
+    loopback_sign_in(server_url, wait, open_browser).await
+}
+
+async fn loopback_sign_in(server_url: &str, wait: Duration, open: impl FnOnce(&str) -> bool) -> Result<String, Box<dyn std::error::Error>> {
+    let listener = TcpListener::bind(("127.0.0.1", 0)).await?;
+    let port = listener.local_addr()?.port();
+    let state = random_state();
+    let url = format!(
+        "{}/auth/signin?source=desktop&redirect_port={}&state={}",
+        server_url.trim_end_matches('/'),
+        port,
+        state
+    );
+
+    if !open(&url) {
+        return Err("no browser available".into());
+    }
+    println!("Opened your browser to sign in. If nothing happened, visit:");
+    println!("{}", url);
+
+    match tokio::time::timeout(wait, wait_for_callback(listener, &state)).await {
+        Ok(result) => result,
+        Err(_) => Err("timed out waiting for the browser".into()),
+    }
+}
+
+#[derive(Deserialize)]
+struct DeviceAuthorization {
+    device_code: String,
+    user_code: String,
+    verification_uri: String,
+    verification_uri_complete: Option<String>,
+    expires_in: u64,
+    interval: Option<u64>,
+}
+
+#[derive(Deserialize, Default)]
+struct DeviceTokenResponse {
+    access_token: Option<String>,
+    error: Option<String>,
+    error_description: Option<String>,
+}
+
+// Device authorization flow for machines without a browser
+pub async fn login_with_device_code(server_url: &str) -> Result<String, Box<dyn std::error::Error>> {
+    let client = reqwest::Client::new();
+    let authorization: DeviceAuthorization = client
+        .post(format!("{}/api/auth/device", server_url))
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+
+    println!("To sign in, open this page on any device:");
+    println!();
+    println!("    {}", authorization.verification_uri);
+    println!();
+    println!("and enter the code:  {}", authorization.user_code);
+    if let Some(complete) = &authorization.verification_uri_complete {
+        println!("(or open {} directly)", complete);
+    }
+    println!();
+    println!("Waiting for approval...");
+
+    let token_url = format!("{}/api/auth/device/token", server_url);
+    let deadline = Instant::now() + Duration::from_secs(authorization.expires_in);
+    let interval = Duration::from_secs(authorization.interval.unwrap_or(5).max(1));
+    poll_for_token(&client, &token_url, &authorization.device_code, interval, deadline).await
+}
+
+// Poll the token endpoint until the code is approved, denied or expires
+async fn poll_for_token(
+    client: &reqwest::Client,
+    token_url: &str,
+    device_code: &str,
+    mut interval: Duration,
+    deadline: Instant,
+) -> Result<String, Box<dyn std::error::Error>> {
+    loop {
+        tokio::time::sleep(interval).await;
+        if Instant::now() >= deadline {
+            return Err("the code expired before it was approved, run login again".into());
+        }
+
+        let response = match client
+            .post(token_url)
+            .form(&[("grant_type", DEVICE_GRANT_TYPE), ("device_code", device_code)])
+            .send()
+            .await
+        {
+            Ok(response) => response,
+            Err(_) => {
+                // Network blip: back off and keep polling until the code expires
+                interval = (interval * 2).min(MAX_POLL_INTERVAL);
+                continue;
+            }
+        };
+        let status = response.status();
+        let body: DeviceTokenResponse = match response.json().await {
+            Ok(body) if !status.is_server_error() => body,
+            // A proxy's error page or an overloaded server: the same as a blip
+            _ => {
+                interval = (interval * 2).min(MAX_POLL_INTERVAL);
+                continue;
+            }
+        };
+
+        if let Some(token) = body.access_token.filter(|t| !t.is_empty()) {
+            return Ok(token);
+        }
+        match body.error.as_deref() {
+            Some("authorization_pending") => {}
+            Some("slow_down") => interval = (interval + SLOW_DOWN_STEP).min(MAX_POLL_INTERVAL),
+            Some("expired_token") => return Err("the code expired before it was approved, run login again".into()),
+            Some("access_denied") => return Err("sign-in was denied".into()),
+            other => {
+                let reason = body.error_description.as_deref().or(other).unwrap_or("unexpected response");
+                return Err(format!("device sign-in failed ({}): {}", status, reason).into());
+            }
+        }
+    }
+}
+
+// Headless fallback: sign in on any device and paste the token
+pub fn read_token_from_console(server_url: &str) -> Result<String, Box<dyn std::error::Error>> {
+    println!("Sign in on any device with a browser, then:");
+    println!("1. Visit: {}/token", server_url.trim_end_matches('/'));
+    println!("2. Generate and copy your sync token");
+    println!("3. Paste it here");
+    println!();
+    loop {
+        print!("Enter your sync token: ");
+        io::stdout().flush()?;
+
+        let mut token = String::new();
+        // 0 bytes: stdin is closed (no console), asking again won't help
+        if io::stdin().read_line(&mut token)? == 0 {
+            return Err("no token entered".into());
+        }
+        if !token.trim().is_empty() {
+            return Ok(token.trim().to_string());
+        }
+    }
+}
+
+// -------------------- token expiry --------------------
+// Sync tokens are JWTs with an `exp` claim. We only read the claim to warn
+// before the token runs out; checking the signature is the server's job.
+
+// Start warning this many days before the token expires
+pub const EXPIRY_WARNING_DAYS: i64 = 7;
+
+#[derive(Deserialize)]
+struct Claims {
+    exp: Option<i64>,
+}
+
+pub fn token_expiry(token: &str) -> Option<DateTime<Utc>> {
+    let payload = token.split('.').nth(1)?;
+    let bytes = URL_SAFE_NO_PAD.decode(payload.trim_end_matches('=')).ok()?;
+    let claims: Claims = serde_json::from_slice(&bytes).ok()?;
+    DateTime::from_timestamp(claims.exp?, 0)
+}
+
+// A warning to show when the token expires soon (or already has)
+pub fn expiry_warning(token: &str) -> Option<String> {
+    let expires = token_expiry(token)?;
+    let left = expires - Utc::now();
+    if left.num_seconds() <= 0 {
+        Some("Your sync token has expired. Run `chronos login` to keep syncing.".to_string())
+    } else if left.num_days() < EXPIRY_WARNING_DAYS {
+        Some(format!(
+            "Your sync token expires in {} (on {}). Run `chronos login` to renew it.",
+            if left.num_days() >= 2 { format!("{} days", left.num_days()) } else { format!("{} hours", left.num_hours().max(1)) },
+            expires.with_timezone(&chrono::Local).format("%Y-%m-%d %H:%M")
+        ))
+    } else {
+        None
+    }
+}
+
+// Explicit `chronos login [--device]`
+pub async fn login(server_url: &str, device: bool) -> Result<String, Box<dyn std::error::Error>> {
+    if device {
+        login_with_device_code(server_url).await
+    } else {
+        login_with_loopback(server_url, LOOPBACK_TIMEOUT).await
+    }
+}
+
+// Sign in again from the running tracker after the server rejected the
+// token. Browser only: there may be no console to show a device code on.
+pub async fn renew_in_browser(server_url: String) -> Result<String, String> {
+    login_with_loopback(&server_url, LOOPBACK_TIMEOUT).await.map_err(|e| e.to_string())
+}
+
+// First-run setup: browser if we can, device code if not, pasting as a last resort
+pub async fn sign_in(server_url: &str) -> Result<String, Box<dyn std::error::Error>> {
+    println!("🚀 Welcome to Chronos!");
+    println!("Setting up your account for the first time...");
+    println!();
+
+    match login_with_loopback(server_url, LOOPBACK_TIMEOUT).await {
+        Ok(token) => return Ok(token),
+        Err(e) => println!("Browser sign-in unavailable ({}).", e),
+    }
+    match login_with_device_code(server_url).await {
+        Ok(token) => return Ok(token),
+        Err(e) => println!("Device sign-in failed ({}).", e),
+    }
+    read_token_from_console(server_url)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Stands in for the dashboard: the sign-in page sends the browser to
+    // the loopback callback, first with a wrong state (a stale tab) and
+    // then with the right one and a token
+    async fn serve_sign_in(listener: TcpListener) {
+        for attempt in 0..2 {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let target = read_request_target(&mut stream).await.unwrap();
+            let query = target.strip_prefix("/auth/signin?").unwrap();
+            let port = query_param(query, "redirect_port").unwrap();
+            let state = if attempt == 0 { "stale".to_string() } else { query_param(query, "state").unwrap() };
+            let location = format!("http://127.0.0.1:{}/callback?state={}&token=%20header.pay%2Bload.sig%20", port, state);
+            let response = format!("HTTP/1.1 302 Found\r\nLocation: {}\r\nContent-Length: 0\r\nConnection: close\r\n\r\n", location);
+            stream.write_all(response.as_bytes()).await.unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn loopback_sign_in_receives_token() {
+        let server = TcpListener::bind(("127.0.0.1", 0)).await.unwrap();
+        let server_url = format!("http://127.0.0.1:{}/", server.local_addr().unwrap().port());
+        tokio::spawn(serve_sign_in(server));
+
+        // follows the redirects, like a browser; the statuses of the pages it ends on
+        let mut browser = None;
+        let open = |url: &str| {
+            let url = url.to_string();
+            browser = Some(tokio::spawn(async move {
+                let mut statuses = Vec::new();
+                for _ in 0..2 {
+                    statuses.push(reqwest::get(&url).await.unwrap().status().as_u16());
+                }
+                statuses
+            }));
+            true
+        };
+        let token = loopback_sign_in(&server_url, Duration::from_secs(10), open).await.unwrap();
+        assert_eq!(token, "header.pay+load.sig");
+        assert_eq!(browser.unwrap().await.unwrap(), vec![400, 200]);
+    }
+
+    // Answers each poll of the token endpoint in turn, noting when it came
+    async fn serve_token(listener: TcpListener, answers: Vec<(&'static str, &'static str)>) -> Vec<tokio::time::Instant> {
+        let mut polls = Vec::new();
+        for (status, body) in answers {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            polls.push(tokio::time::Instant::now());
+            // read the whole form, or closing the socket resets the connection
+            let mut request = Vec::new();
+            let mut chunk = [0u8; 1024];
+            loop {
+                let n = stream.read(&mut chunk).await.unwrap();
+                request.extend_from_slice(&chunk[..n]);
+                let text = String::from_utf8_lossy(&request);
+                if let Some((head, form)) = text.split_once("\r\n\r\n") {
+                    let length = head
+                        .lines()
+                        .find_map(|line| line.to_lowercase().strip_prefix("content-length:").map(|n| n.trim().parse::<usize>().unwrap()))
+                        .unwrap_or(0);
+                    if form.len() >= length {
+                        assert!(form.contains("device_code=dev-123"));
+                        break;
+                    }
+                }
+                assert!(n > 0);
+            }
+            let content_type = if body.starts_with('{') { "application/json" } else { "text/html" };
+            let response = format!(
+                "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                status,
+                content_type,
+                body.len(),
+                body
+            );
+            stream.write_all(response.as_bytes()).await.unwrap();
+        }
+        polls
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn device_sign_in_backs_off() {
+        let listener = TcpListener::bind(("127.0.0.1", 0)).await.unwrap();
+        let token_url = format!("http://127.0.0.1:{}/api/auth/device/token", listener.local_addr().unwrap().port());
+        let server = tokio::spawn(serve_token(
+            listener,
+            vec![
+                ("400 Bad Request", r#"{"error":"authorization_pending"}"#),
+                ("400 Bad Request", r#"{"error":"slow_down"}"#),
+                ("503 Service Unavailable", "<html><body>Service Unavailable</body></html>"),
+                ("200 OK", r#"{"access_token":"header.payload.sig","token_type":"Bearer"}"#),
+            ],
+        ));
+
+        let start = tokio::time::Instant::now();
+        let deadline = Instant::now() + Duration::from_secs(600);
+        let token = poll_for_token(&reqwest::Client::new(), &token_url, "dev-123", Duration::from_secs(2), deadline).await.unwrap();
+        assert_eq!(token, "header.payload.sig");
+
+        // waits of 2s, 2s, 2s + 5s after slow_down, then doubled after the 503
+        let polls = server.await.unwrap();
+        let mut previous = start;
+        let waits: Vec<u64> = polls
+            .iter()
+            .map(|poll| {
+                let wait = (*poll - previous).as_secs();
+                previous = *poll;
+                wait
+            })
+            .collect();
+        assert_eq!(waits, vec![2, 2, 7, 14]);
+    }
+
+    #[tokio::test]
+    async fn loopback_sign_in_without_browser() {
+        let result = loopback_sign_in("http://127.0.0.1:9", Duration::from_secs(1), |_| false).await;
+        assert_eq!(result.unwrap_err().to_string(), "no browser available");
+    }
+
+    #[tokio::test]
+    async fn loopback_sign_in_times_out() {
+        let result = loopback_sign_in("http://127.0.0.1:9", Duration::from_millis(200), |_| true).await;
+        assert_eq!(result.unwrap_err().to_string(), "timed out waiting for the browser");
+    }
+}
@@ -0,0 +1,257 @@
+use chrono::NaiveDateTime;
+use std::collections::HashMap;
+
+use crate::entries::{data_str, entry_time};
+use crate::urls::domain_of;
+use crate::LogEntry;
+
+// -------------------- browser focus attribution --------------------
+// Window events tell us how long a browser was focused, browser events tell
+// us which pages were visited. This joins the two so focused browser time can
+// be attributed to the page (and domain) that was actually on screen.
+
+// Map a process name to the browserType used in browser log entries
+pub fn browser_family(process_name: &str) -> Option<&'static str> {
+    let p = process_name.to_lowercase();
+    if p.contains("chrome") || p.contains("msedge") || p.contains("brave") {
+        Some("Chromium")
+    } else if p.contains("firefox") {
+        Some("Firefox")
+    } else {
+        None
+    }
+}
+
+struct Visit {
+    entry: usize,
+    at: NaiveDateTime,
+    family: String,
+    title: String,
+}
+
+struct FocusSession {
+    // start of the window session before, which bounds the visits a
+    // browser's first session can show
+    before: NaiveDateTime,
+    start: NaiveDateTime,
+    end: NaiveDateTime,
+    family: &'static str,
+    title: String,
+}
+
+// Visit time in local time. The history databases give us the real visit
+// time (stored as visitedAt, UTC); fall back to when the line was logged.
+fn visit_time(entry: &LogEntry) -> Option<NaiveDateTime> {
+    if let Some(visited) = entry.data.get("visitedAt").and_then(|v| v.as_str()) {
+        if let Ok(utc) = chrono::DateTime::parse_from_rfc3339(visited) {
+            return Some(utc.with_timezone(&chrono::Local).naive_local());
+        }
+    }
+    entry_time(entry)
+}
+
+// Browser windows are titled "<page title> - Google Chrome" and similar
+fn title_matches(window_title: &str, page_title: &str) -> bool {
+    !page_title.is_empty() && window_title.starts_with(page_title)
+}
+
+// Seconds of focused browser time attributed to each browser entry, keyed by
+// index into `entries`. The last window session is still open (we don't know
+// when it ends yet) so it is not attributed.
+//
+// A session can show the pages visited since its browser was last focused
+// (a link opened from another app is visited just before the browser takes
+// focus) and the page it was left on. Older visits are gone from the screen
+// even when their title comes back.
+pub fn attribute_focus_time(entries: &[LogEntry]) -> HashMap<usize, i64> {
+    // tracker events (pause, stop) end the session before them
+    let mut windows: Vec<(NaiveDateTime, &LogEntry)> = entries
+        .iter()
+        .filter(|e| e.log_type == "window" || e.log_type == "tracker")
+        .filter_map(|e| entry_time(e).map(|t| (t, e)))
+        .collect();
+    windows.sort_by_key(|(t, _)| *t);
+
+    let sessions: Vec<FocusSession> = (1..windows.len())
+        .filter_map(|i| {
+            let (start, entry) = windows[i - 1];
+            let (end, _) = windows[i];
+            let family = browser_family(data_str(entry, "processName"))?;
+            Some(FocusSession {
+                before: windows[i.saturating_sub(2)].0,
+                start,
+                end,
+                family,
+                title: data_str(entry, "windowTitle").to_string(),
+            })
+        })
+        .filter(|s| s.end > s.start)
+        .collect();
+
+    let mut visits: Vec<Visit> = entries
+        .iter()
+        .enumerate()
+        .filter(|(_, e)| e.log_type == "browser")
+        .filter_map(|(i, e)| {
+            Some(Visit {
+                entry: i,
+                at: visit_time(e)?,
+                family: data_str(e, "browserType").to_string(),
+                title: data_str(e, "browserTitle").to_string(),
+            })
+        })
+        .collect();
+    visits.sort_by_key(|v| v.at);
+
+    let mut attributed: HashMap<usize, i64> = HashMap::new();
+    // per browser: when its last session ended and the page it showed then
+    let mut left: HashMap<&str, (NaiveDateTime, Option<&Visit>)> = HashMap::new();
+    for session in &sessions {
+        let (since, shown) = left.get(session.family).copied().unwrap_or((session.before, None));
+        // `shown` was visited before `since`, so this stays in time order
+        let candidates: Vec<&Visit> = shown
+            .into_iter()
+            .chain(visits.iter().filter(|v| v.family == session.family && v.at >= since && v.at < session.end))
+            .collect();
+
+        // The title only changes when the page does, so if a visit matches
+        // the window title that page was on screen for the whole session.
+        if let Some(v) = candidates.iter().rev().find(|v| title_matches(&session.title, &v.title)) {
+            *attributed.entry(v.entry).or_insert(0) += (session.end - session.start).num_milliseconds();
+            left.insert(session.family, (session.end, Some(v)));
+            continue;
+        }
+
+        // Otherwise walk the session, switching page at each visit
+        let mut current = candidates.iter().rev().find(|v| v.at <= session.start).copied();
+        let mut from = session.start;
+        for v in candidates.iter().filter(|v| v.at > session.start) {
+            if let Some(shown) = current {
+                *attributed.entry(shown.entry).or_insert(0) += (v.at - from).num_milliseconds();
+            }
+            current = Some(v);
+            from = v.at;
+        }
+        if let Some(shown) = current {
+            *attributed.entry(shown.entry).or_insert(0) += (session.end - from).num_milliseconds();
+        }
+        left.insert(session.family, (session.end, current));
+    }
+
+    // summed in milliseconds so short sessions aren't lost to rounding
+    attributed.into_iter().map(|(idx, millis)| (idx, (millis + 500) / 1000)).collect()
+}
+
+// Annotate browser entries with their domain and attributed focus time
+pub fn annotate_browser_entries(entries: &mut [LogEntry]) {
+    let attributed = attribute_focus_time(entries);
+    for (i, entry) in entries.iter_mut().enumerate() {
+        if entry.log_type != "browser" {
+            continue;
+        }
+        let domain = domain_of(data_str(entry, "url"));
+        if let serde_json::Value::Object(data) = &mut entry.data {
+            if let Some(domain) = domain {
+                data.insert("domain".to_string(), serde_json::Value::String(domain));
+            }
+            let seconds = attributed.get(&i).copied().unwrap_or(0);
+            data.insert("focusSeconds".to_string(), serde_json::Value::from(seconds));
+        }
+    }
+}
+
+// Total focused seconds per domain, largest first
+pub fn domain_durations(entries: &[LogEntry]) -> Vec<(String, i64)> {
+    let mut totals: HashMap<String, i64> = HashMap::new();
+    for (i, seconds) in attribute_focus_time(entries) {
+        if let Some(domain) = domain_of(data_str(&entries[i], "url")) {
+            *totals.entry(domain).or_insert(0) += seconds;
+        }
+    }
+    let mut out: Vec<(String, i64)> = totals.into_iter().filter(|(_, s)| *s > 0).collect();
+    out.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn window(timestamp: &str, process: &str, title: &str) -> LogEntry {
+        LogEntry { timestamp: timestamp.to_string(), log_type: "window".to_string(), data: json!({ "processName": process, "windowTitle": title }) }
+    }
+
+    fn visit(timestamp: &str, title: &str, url: &str) -> LogEntry {
+        LogEntry {
+            timestamp: timestamp.to_string(),
+            log_type: "browser".to_string(),
+            data: json!({ "browserType": "Chromium", "browserTitle": title, "url": url }),
+        }
+    }
+
+    fn stop(timestamp: &str) -> LogEntry {
+        LogEntry { timestamp: timestamp.to_string(), log_type: "tracker".to_string(), data: json!({ "event": "stopped" }) }
+    }
+
+    fn expected(durations: &[(&str, i64)]) -> Vec<(String, i64)> {
+        durations.iter().map(|(domain, seconds)| (domain.to_string(), *seconds)).collect()
+    }
+
+    #[test]
+    fn title_matched_sessions() {
+        let entries = vec![
+            window("2025-09-02 10:00:00", "chrome.exe", "Inbox - Gmail - Google Chrome"),
+            visit("2025-09-02 10:00:05", "Inbox - Gmail", "https://mail.google.com/mail/u/0/#inbox"),
+            // loaded in a background tab
+            visit("2025-09-02 10:01:00", "std - Rust", "https://doc.rust-lang.org/std/"),
+            window("2025-09-02 10:10:00", "Code.exe", "main.rs - chronos - Visual Studio Code"),
+            // back to the tab left open, without a new visit
+            window("2025-09-02 10:20:00", "chrome.exe", "Inbox - Gmail - Google Chrome"),
+            window("2025-09-02 10:30:00", "Code.exe", "main.rs - chronos - Visual Studio Code"),
+            stop("2025-09-02 10:40:00"),
+        ];
+        assert_eq!(domain_durations(&entries), expected(&[("mail.google.com", 1200)]));
+    }
+
+    #[test]
+    fn page_switches_within_a_session() {
+        let mut entries = vec![
+            window("2025-09-02 10:00:00", "chrome", "New Tab - Google Chrome"),
+            visit("2025-09-02 10:00:00", "Example", "https://www.example.com/"),
+            visit("2025-09-02 10:05:00", "Docs", "https://docs.rs/serde"),
+            window("2025-09-02 10:15:00", "code", "main.rs - chronos - Visual Studio Code"),
+            stop("2025-09-02 10:20:00"),
+        ];
+        assert_eq!(domain_durations(&entries), expected(&[("docs.rs", 600), ("www.example.com", 300)]));
+
+        annotate_browser_entries(&mut entries);
+        assert_eq!(entries[1].data["domain"], "www.example.com");
+        assert_eq!(entries[1].data["focusSeconds"], 300);
+        assert_eq!(entries[2].data["focusSeconds"], 600);
+    }
+
+    #[test]
+    fn stale_visits() {
+        let entries = vec![
+            visit("2025-09-01 09:00:00", "Inbox - Gmail", "https://mail.google.com/mail/u/0/#inbox"),
+            window("2025-09-01 09:05:00", "code", "main.rs - chronos - Visual Studio Code"),
+            stop("2025-09-01 18:00:00"),
+            window("2025-09-02 10:00:00", "chrome", "Inbox - Gmail - Google Chrome"),
+            stop("2025-09-02 10:10:00"),
+        ];
+        assert_eq!(domain_durations(&entries), expected(&[]));
+
+        // once the browser has shown another page, the old one is gone: a
+        // title matching nothing it can show leaves the time with that page
+        let entries = vec![
+            window("2025-09-02 09:00:00", "chrome", "Hacker News - Google Chrome"),
+            visit("2025-09-02 09:00:00", "Inbox - Gmail", "https://mail.google.com/mail/u/0/#inbox"),
+            visit("2025-09-02 09:10:00", "Hacker News", "https://news.ycombinator.com/"),
+            window("2025-09-02 09:20:00", "code", "main.rs - chronos - Visual Studio Code"),
+            window("2025-09-02 10:00:00", "chrome", "Inbox - Gmail - Google Chrome"),
+            stop("2025-09-02 10:10:00"),
+        ];
+        assert_eq!(domain_durations(&entries), expected(&[("news.ycombinator.com", 1800)]));
+    }
+}
@@ -0,0 +1,390 @@
+use regex::{Captures, Regex};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::sync::OnceLock;
+
+use crate::config::Config;
+use crate::editors::{VIM, VS_CODE};
+use crate::git::home_dir;
+use crate::log_line;
+use crate::process::{jetbrains_names, process_name, TERMINALS};
+use crate::urls::{bare_domain, domain_of};
+use crate::LogEntry;
+
+// -------------------- categories --------------------
+// Window and browser entries get a category ("Work > Coding > Rust") and a
+// productivity score from ordered rules; the first rule that matches wins.
+// Rules from the "categories" setting come before the built-in ones, which
+// can be turned off. Like issues, categories are worked out when entries are
+// read back from the log, so changed rules apply to all stored history and
+// the next sync sends it recategorised.
+//
+// Scores run from -2 (very distracting) to 2 (very productive).
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default, rename_all = "camelCase")]
+pub struct CategoryConfig {
+    pub built_in_rules: bool,
+    pub rules: Vec<CategoryRule>,
+}
+
+impl Default for CategoryConfig {
+    fn default() -> Self {
+        CategoryConfig { built_in_rules: true, rules: Vec::new() }
+    }
+}
+
+// Every criterion that is set must match, e.g.
+// { "path": "~/src/chronos", "category": "Work > Chronos > {language}", "productivity": 2 }.
+// "{language}", "{project}", "{repository}", "{app}" and "{domain}" in the
+// category are filled in from the entry; a part naming something the entry
+// doesn't have is left out, so "Work > Coding > {language}" is "Work > Coding"
+// outside an editor.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(default, rename_all = "camelCase")]
+pub struct CategoryRule {
+    // Process name or app identity (see process), case-insensitive, ".exe" optional
+    pub process: Option<String>,
+    // Regex matched against the window title (or page title for visits)
+    pub title_pattern: Option<String>,
+    // Domain, also matches its subdomains
+    pub domain: Option<String>,
+    // Directory containing the working directory, the executable, the git
+    // repository or the file or project an editor shows; "~" is the home
+    // directory
+    pub path: Option<String>,
+    pub category: String,
+    // 0 when unset
+    pub productivity: Option<i64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Category {
+    pub name: String,
+    pub productivity: i64,
+}
+
+const OTHER_EDITORS: &[&str] = &["devenv", "emacs", "helix", "hx", "notepad++", "sublime_text", "xcode", "zed"];
+
+// (processes, domains, category, productivity), in order. The app and the
+// site each make a rule of their own: window entries have no URL and visits
+// no process.
+const BUILT_IN: &[(&[&str], &[&str], &str, i64)] = &[
+    (&[], &["github.com", "gitlab.com", "bitbucket.org"], "Work > Coding > Code review", 2),
+    (
+        &[],
+        &["docs.rs", "crates.io", "doc.rust-lang.org", "stackoverflow.com", "developer.mozilla.org", "docs.python.org", "pypi.org", "npmjs.com", "pkg.go.dev"],
+        "Work > Coding > Reference",
+        2,
+    ),
+    (&[], &["atlassian.net", "linear.app"], "Work > Planning", 1),
+    (&["figma"], &["figma.com"], "Work > Design", 2),
+    (
+        &["slack", "teams", "ms-teams", "zoom", "outlook", "olk", "thunderbird"],
+        &["slack.com", "teams.microsoft.com", "zoom.us", "mail.google.com", "outlook.office.com", "outlook.live.com", "meet.google.com"],
+        "Work > Communication",
+        1,
+    ),
+    (
+        &["winword", "excel", "powerpnt", "soffice", "libreoffice", "obsidian", "notion"],
+        &["docs.google.com", "notion.so", "confluence.com"],
+        "Work > Documents",
+        1,
+    ),
+    (&["discord", "telegram-desktop", "signal", "whatsapp"], &["discord.com", "web.whatsapp.com"], "Communication > Chat", 0),
+    (&["spotify"], &["open.spotify.com", "music.youtube.com"], "Entertainment > Music", 0),
+    (&[], &["youtube.com", "netflix.com", "twitch.tv", "primevideo.com", "disneyplus.com"], "Entertainment > Video", -2),
+    (&["steam", "steamwebhelper"], &["store.steampowered.com"], "Entertainment > Games", -2),
+    (&[], &["news.ycombinator.com", "lobste.rs", "bbc.co.uk", "nytimes.com", "theguardian.com"], "News", -1),
+    (&[], &["reddit.com", "twitter.com", "x.com", "facebook.com", "instagram.com", "tiktok.com", "linkedin.com"], "Social", -2),
+];
+
+struct CompiledRule {
+    processes: Vec<String>,
+    title: Option<Regex>,
+    domains: Vec<String>,
+    paths: Vec<String>,
+    category: String,
+    productivity: i64,
+}
+
+// Entry fields a path rule looks at. Editors' files and projects are often
+// bare names, which match no rule directory.
+const PATH_KEYS: [&str; 5] = ["workingDirectory", "exePath", "repositoryPath", "file", "project"];
+
+// Forward slashes, no trailing one, "~" expanded; case-insensitive on Windows
+fn normalise_path(path: &str) -> String {
+    let path = match path.strip_prefix('~') {
+        Some(rest) => match home_dir() {
+            Some(home) => format!("{}{}", home.to_string_lossy(), rest),
+            None => path.to_string(),
+        },
+        None => path.to_string(),
+    };
+    let path = path.replace('\\', "/").trim_end_matches('/').to_string();
+    if cfg!(windows) {
+        path.to_lowercase()
+    } else {
+        path
+    }
+}
+
+fn field<'a>(data: &'a Map<String, Value>, key: &str) -> Option<&'a str> {
+    data.get(key).and_then(|v| v.as_str()).filter(|text| !text.is_empty())
+}
+
+impl CompiledRule {
+    fn compile(rule: &CategoryRule) -> Option<CompiledRule> {
+        if rule.category.trim().is_empty() {
+            log_line("Ignoring category rule without a category");
+            return None;
+        }
+        let title = match &rule.title_pattern {
+            Some(pattern) => match Regex::new(pattern) {
+                Ok(re) => Some(re),
+                Err(e) => {
+                    log_line(&format!("Ignoring category rule with invalid title pattern '{}': {}", pattern, e));
+                    return None;
+                }
+            },
+            None => None,
+        };
+        Some(CompiledRule {
+            processes: rule.process.iter().map(|name| process_name(name)).collect(),
+            title,
+            domains: rule.domain.iter().map(|domain| bare_domain(domain)).collect(),
+            paths: rule.path.iter().map(|path| normalise_path(path)).collect(),
+            category: rule.category.clone(),
+            productivity: rule.productivity.unwrap_or(0).clamp(-2, 2),
+        })
+    }
+
+    fn matches(&self, log_type: &str, data: &Map<String, Value>) -> bool {
+        let (title, url) = match log_type {
+            "window" => (field(data, "windowTitle"), None),
+            _ => (field(data, "browserTitle"), field(data, "url")),
+        };
+        if !self.processes.is_empty() {
+            let names: Vec<String> = ["processName", "app"].iter().filter_map(|key| field(data, key)).map(process_name).collect();
+            if !self.processes.iter().any(|wanted| names.contains(wanted)) {
+                return false;
+            }
+        }
+        if let Some(re) = &self.title {
+            if !title.is_some_and(|title| re.is_match(title)) {
+                return false;
+            }
+        }
+        if !self.domains.is_empty() {
+            let host = match url.and_then(domain_of) {
+                Some(host) => bare_domain(&host),
+                None => return false,
+            };
+            if !self.domains.iter().any(|wanted| host == *wanted || host.ends_with(&format!(".{}", wanted))) {
+                return false;
+            }
+        }
+        if !self.paths.is_empty() {
+            let paths: Vec<String> = PATH_KEYS.iter().filter_map(|key| field(data, key)).map(normalise_path).collect();
+            let within = |path: &String, dir: &String| path == dir || path.starts_with(&format!("{}/", dir));
+            if !self.paths.iter().any(|dir| paths.iter().any(|path| within(path, dir))) {
+                return false;
+            }
+        }
+        true
+    }
+
+    // The category with its placeholders filled in
+    fn name(&self, log_type: &str, data: &Map<String, Value>) -> String {
+        static PLACEHOLDER: OnceLock<Regex> = OnceLock::new();
+        let placeholder = PLACEHOLDER.get_or_init(|| Regex::new(r"\{(\w+)\}").unwrap());
+        let value = |key: &str| -> Option<String> {
+            match key {
+                "domain" => field(data, "domain").map(str::to_string).or_else(|| field(data, "url").and_then(domain_of)),
+                "app" if log_type == "window" => field(data, "app").or_else(|| field(data, "processName")).map(str::to_string),
+                "app" => field(data, "browserType").map(str::to_string),
+                _ => field(data, key).map(str::to_string),
+            }
+        };
+        self.category
+            .split('>')
+            .filter_map(|part| {
+                let mut missing = false;
+                let part = placeholder.replace_all(part.trim(), |caps: &Captures| {
+                    value(&caps[1]).unwrap_or_else(|| {
+                        missing = true;
+                        String::new()
+                    })
+                });
+                Some(part.trim().to_string()).filter(|part| !missing && !part.is_empty())
+            })
+            .collect::<Vec<_>>()
+            .join(" > ")
+    }
+}
+
+fn built_in_rules() -> Vec<CompiledRule> {
+    let names = |names: &[&str]| names.iter().map(|name| name.to_string()).collect::<Vec<_>>();
+    let editors: Vec<String> = [VS_CODE, VIM, OTHER_EDITORS].concat().iter().map(|name| name.to_string()).chain(jetbrains_names()).collect();
+    let rule = |processes: Vec<String>, domains: Vec<String>, category: &str, productivity: i64| CompiledRule {
+        processes,
+        title: None,
+        domains,
+        paths: Vec::new(),
+        category: category.to_string(),
+        productivity,
+    };
+    let mut rules = vec![
+        rule(editors, Vec::new(), "Work > Coding > {language}", 2),
+        rule(names(TERMINALS), Vec::new(), "Work > Coding > Terminal", 1),
+    ];
+    for (processes, domains, category, productivity) in BUILT_IN {
+        if !processes.is_empty() {
+            rules.push(rule(names(processes), Vec::new(), category, *productivity));
+        }
+        if !domains.is_empty() {
+            rules.push(rule(Vec::new(), names(domains), category, *productivity));
+        }
+    }
+    rules
+}
+
+pub struct Categoriser {
+    rules: Vec<CompiledRule>,
+}
+
+impl Categoriser {
+    pub fn from_config(config: &Config) -> Categoriser {
+        let mut rules: Vec<CompiledRule> = config.categories.rules.iter().filter_map(CompiledRule::compile).collect();
+        if config.categories.built_in_rules {
+            rules.extend(built_in_rules());
+        }
+        Categoriser { rules }
+    }
+
+    // `log_type` is "window" or "browser"; `data` as in the log entries
+    pub fn categorise(&self, log_type: &str, data: &Map<String, Value>) -> Option<Category> {
+        let rule = self.rules.iter().find(|rule| rule.matches(log_type, data))?;
+        Some(Category { name: rule.name(log_type, data), productivity: rule.productivity }).filter(|category| !category.name.is_empty())
+    }
+
+    // Set "category" and "productivity" on the window and browser entries a
+    // rule matches, replacing what an earlier read set
+    pub fn tag(&self, entries: &mut [LogEntry]) {
+        for entry in entries.iter_mut().filter(|e| e.log_type == "window" || e.log_type == "browser") {
+            let Value::Object(data) = &mut entry.data else {
+                continue;
+            };
+            data.remove("category");
+            data.remove("productivity");
+            if let Some(category) = self.categorise(&entry.log_type, data) {
+                data.insert("category".to_string(), Value::String(category.name));
+                data.insert("productivity".to_string(), Value::from(category.productivity));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn categorise(categoriser: &Categoriser, log_type: &str, data: Value) -> Option<(String, i64)> {
+        let Value::Object(data) = data else { unreachable!() };
+        categoriser.categorise(log_type, &data).map(|category| (category.name, category.productivity))
+    }
+
+    fn some(name: &str, productivity: i64) -> Option<(String, i64)> {
+        Some((name.to_string(), productivity))
+    }
+
+    #[test]
+    fn built_in_apps_and_sites() {
+        let categoriser = Categoriser::from_config(&Config::default());
+        let communication = some("Work > Communication", 1);
+        assert_eq!(categorise(&categoriser, "window", json!({ "processName": "slack", "windowTitle": "general - Acme - Slack" })), communication);
+        assert_eq!(categorise(&categoriser, "window", json!({ "processName": "Slack.exe" })), communication);
+        assert_eq!(categorise(&categoriser, "browser", json!({ "browserTitle": "general - Slack", "url": "https://app.slack.com/client/T1/C2" })), communication);
+        assert_eq!(categorise(&categoriser, "browser", json!({ "url": "https://www.youtube.com/watch?v=1" })), some("Entertainment > Video", -2));
+        assert_eq!(
+            categorise(&categoriser, "window", json!({ "processName": "code", "language": "Rust" })),
+            some("Work > Coding > Rust", 2)
+        );
+        assert_eq!(categorise(&categoriser, "window", json!({ "processName": "java", "app": "PyCharm" })), some("Work > Coding", 2));
+        assert_eq!(categorise(&categoriser, "window", json!({ "processName": "gimp" })), None);
+    }
+
+    #[test]
+    fn path_rules() {
+        let mut config = Config::default();
+        config.categories.rules.push(CategoryRule {
+            path: Some("/src/chronos/".to_string()),
+            category: "Work > Chronos > {language}".to_string(),
+            productivity: Some(2),
+            ..Default::default()
+        });
+        let categoriser = Categoriser::from_config(&config);
+        let chronos = some("Work > Chronos", 2);
+        assert_eq!(categorise(&categoriser, "window", json!({ "processName": "gimp", "workingDirectory": "/src/chronos/rust-client" })), chronos);
+        assert_eq!(categorise(&categoriser, "window", json!({ "processName": "gimp", "repositoryPath": "/src/chronos" })), chronos);
+        assert_eq!(
+            categorise(&categoriser, "window", json!({ "processName": "hx", "file": "/src/chronos/main.rs", "language": "Rust" })),
+            some("Work > Chronos > Rust", 2)
+        );
+        assert_eq!(categorise(&categoriser, "window", json!({ "processName": "gimp", "workingDirectory": "/src/chronos-old" })), None);
+        // a bare project name is not a path
+        assert_eq!(categorise(&categoriser, "window", json!({ "processName": "gimp", "project": "chronos" })), None);
+        // terminals come from the list process uses
+        assert_eq!(categorise(&categoriser, "window", json!({ "processName": "kgx" })), some("Work > Coding > Terminal", 1));
+    }
+
+    #[test]
+    fn user_rules_come_first() {
+        let mut config = Config::default();
+        config.categories.rules.push(CategoryRule {
+            process: Some("code".to_string()),
+            title_pattern: Some("(?i)slides".to_string()),
+            category: "Work > Presenting".to_string(),
+            productivity: Some(1),
+            ..Default::default()
+        });
+        let categoriser = Categoriser::from_config(&config);
+        assert_eq!(
+            categorise(&categoriser, "window", json!({ "processName": "code", "windowTitle": "Slides.md - talk", "language": "Markdown" })),
+            some("Work > Presenting", 1)
+        );
+        // the built-in rule still covers what the user rule doesn't
+        assert_eq!(
+            categorise(&categoriser, "window", json!({ "processName": "code", "windowTitle": "main.rs - chronos", "language": "Rust" })),
+            some("Work > Coding > Rust", 2)
+        );
+        config.categories.built_in_rules = false;
+        let categoriser = Categoriser::from_config(&config);
+        assert_eq!(categorise(&categoriser, "window", json!({ "processName": "code", "windowTitle": "main.rs - chronos" })), None);
+    }
+
+    #[test]
+    fn tag_replaces_earlier_categories() {
+        let entry = |log_type: &str, data: Value| LogEntry { timestamp: "2026-03-02 09:00:00".to_string(), log_type: log_type.to_string(), data };
+        let mut entries = vec![
+            entry("window", json!({ "processName": "code", "language": "Rust", "category": "Other", "productivity": 0 })),
+            entry("window", json!({ "processName": "gimp", "category": "Work > Design", "productivity": 1 })),
+            entry("browser", json!({ "url": "https://www.youtube.com/watch?v=1" })),
+            entry("tracker", json!({ "event": "started", "category": "Other" })),
+        ];
+        Categoriser::from_config(&Config::default()).tag(&mut entries);
+        let tagged: Vec<(&Value, &Value)> = entries.iter().map(|e| (&e.data["category"], &e.data["productivity"])).collect();
+        assert_eq!(
+            tagged,
+            [
+                (&json!("Work > Coding > Rust"), &json!(2)),
+                // rules that no longer match leave no category behind
+                (&Value::Null, &Value::Null),
+                (&json!("Entertainment > Video"), &json!(-2)),
+                // only window and browser entries are categorised
+                (&json!("Other"), &Value::Null),
+            ]
+        );
+    }
+}
@@ -0,0 +1,534 @@
+use chrono::{Duration, Local, NaiveDate};
+use clap::{Parser, Subcommand, ValueEnum};
+use serde_json::{json, Value};
+use std::error::Error;
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use crate::config::{self, Config};
+use crate::instance::{self, LockInfo};
+use crate::ipc;
+use crate::pause::{self, PauseMode, PauseState};
+use crate::sync_status::{self, SyncStatus};
+use crate::{auth, browser_time, categories, credentials, issues, privacy, report, service, sync_policy, LogEntry};
+
+// -------------------- command line --------------------
+// `chronos` on its own (or `chronos run`) starts tracking; everything else is
+// a one-shot command. Commands that affect a running tracker go through its
+// control API (see ipc) and fall back to the files in the app data directory.
+
+#[derive(Parser)]
+#[command(name = "chronos", version, about = "Tracks the apps and websites you use and syncs them to your Chronos dashboard")]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    #[command(about = "Start tracking (the default)")]
+    Run,
+    #[command(about = "Sign in and store the sync token")]
+    Login {
+        #[arg(long, help = "Show a code to approve on another device instead of opening the browser")]
+        device: bool,
+    },
+    #[command(about = "Remove the stored sync token")]
+    Logout,
+    #[command(about = "Show sign-in, tracking and sync state")]
+    Status,
+    #[command(about = "Stop recording activity until `chronos resume`")]
+    Pause {
+        #[arg(num_args = 0.., help = "When to resume: 30m, 1h30m, until 14:00, until tomorrow")]
+        until: Vec<String>,
+    },
+    #[command(about = "Record time as \"Private\" only, without titles or URLs, until `chronos resume`")]
+    Private {
+        #[arg(num_args = 0.., help = "When to resume: 30m, 1h30m, until 14:00, until tomorrow")]
+        until: Vec<String>,
+    },
+    #[command(about = "Leave pause or private mode and record activity again")]
+    Resume,
+    #[command(about = "Sync the activity log with the server")]
+    Sync {
+        #[arg(long, help = "Sync right away instead of waiting for the tracker")]
+        now: bool,
+    },
+    #[command(about = "Show what the running tracker recorded recently")]
+    Events {
+        #[arg(short = 'n', long, default_value_t = 20, help = "How many events to show")]
+        limit: usize,
+    },
+    #[command(about = "Export the local activity log")]
+    Export {
+        #[arg(long, value_enum, default_value_t = ExportFormat::Json)]
+        format: ExportFormat,
+        #[arg(long, help = "First day to include (YYYY-MM-DD)")]
+        since: Option<NaiveDate>,
+        #[arg(long, help = "Last day to include (YYYY-MM-DD)")]
+        until: Option<NaiveDate>,
+        #[arg(short, long, help = "Write to this file instead of stdout")]
+        output: Option<PathBuf>,
+    },
+    #[command(about = "Show time per app and per website")]
+    Report {
+        #[arg(long, default_value_t = 1, help = "Number of days to cover, ending today")]
+        days: u32,
+    },
+    #[command(about = "Start Chronos on login (Linux)")]
+    Service {
+        #[command(subcommand)]
+        action: ServiceAction,
+    },
+    #[command(about = "Show or change settings in config.json")]
+    Config {
+        #[command(subcommand)]
+        action: Option<ConfigAction>,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum ConfigAction {
+    #[command(about = "Print the effective configuration (the default)")]
+    Show,
+    #[command(about = "Print the path of config.json")]
+    Path,
+    #[command(about = "Print one setting, e.g. `sync.level`")]
+    Get { key: String },
+    #[command(about = "Change one setting, e.g. `sync.level redacted`")]
+    Set { key: String, value: String },
+}
+
+#[derive(Subcommand)]
+pub enum ServiceAction {
+    #[command(about = "Install a systemd user unit, or an XDG autostart entry without systemd")]
+    Install {
+        #[arg(long, help = "Use an XDG autostart entry even when systemd is available")]
+        autostart: bool,
+    },
+    #[command(about = "Stop starting Chronos on login")]
+    Uninstall,
+    #[command(about = "Show how Chronos starts on login")]
+    Status,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum ExportFormat {
+    Json,
+    Csv,
+}
+
+pub async fn execute(command: Command, config: &Config, server_url: &str) -> Result<(), Box<dyn Error>> {
+    match command {
+        Command::Run => crate::run_tracker(config, server_url).await,
+        Command::Login { device } => login(server_url, device).await?,
+        Command::Logout => logout().await?,
+        Command::Status => print_status().await,
+        Command::Pause { until } => pause(PauseMode::Paused, &until.join(" ")).await?,
+        Command::Private { until } => pause(PauseMode::Private, &until.join(" ")).await?,
+        Command::Resume => resume().await?,
+        Command::Sync { now } => sync(config, server_url, now).await?,
+        Command::Events { limit } => print_events(limit).await?,
+        Command::Export { format, since, until, output } => export(config, format, since, until, output)?,
+        Command::Report { days } => print_report(config, days)?,
+        Command::Service { action } => match action {
+            ServiceAction::Install { autostart } => {
+                // started on login there's no console to sign in from
+                if credentials::load_token().await.is_none() {
+                    return Err("sign in first with `chronos login`".into());
+                }
+                service::install(autostart)?
+            }
+            ServiceAction::Uninstall => service::uninstall()?,
+            ServiceAction::Status => service::status()?,
+        },
+        Command::Config { action } => config_command(config, action.unwrap_or(ConfigAction::Show))?,
+    }
+    Ok(())
+}
+
+async fn login(server_url: &str, device: bool) -> Result<(), Box<dyn Error>> {
+    let token = auth::login(server_url, device).await.map_err(|e| format!("sign-in failed: {}", e))?;
+    let backend = credentials::save_token(&token).await?;
+    let mut status = SyncStatus::load();
+    if status.is_paused() {
+        status.token_rejected_at = None;
+        status.save();
+    }
+    println!("✅ Signed in. Chronos will sync with this account.");
+    println!("Token stored in the {}.", backend.name());
+    // A running tracker reads the stored token again when asked to sync
+    if let Some(mut client) = ipc::Client::connect().await {
+        match client.call("sync", Value::Null).await {
+            Ok(_) => println!("Synced."),
+            Err(e) => println!("The running tracker could not sync yet: {}", e),
+        }
+    }
+    Ok(())
+}
+
+async fn logout() -> Result<(), Box<dyn Error>> {
+    credentials::delete_token().await?;
+    SyncStatus::default().save();
+    println!("Signed out. A tracker that is already running keeps its token until it is restarted.");
+    Ok(())
+}
+
+async fn print_status() {
+    let status = SyncStatus::load();
+    match credentials::load_token().await {
+        Some(token) => {
+            println!("Signed in:     yes");
+            match auth::token_expiry(&token) {
+                Some(expires) => println!("Token expires: {}", expires.with_timezone(&Local).format("%Y-%m-%d %H:%M")),
+                None => println!("Token expires: unknown"),
+            }
+            if let Some(warning) = auth::expiry_warning(&token) {
+                println!("⚠️  {}", warning);
+            }
+        }
+        None => println!("Signed in:     no (run `chronos login`)"),
+    }
+    let live = match ipc::Client::connect().await {
+        Some(mut client) => client.call("status", Value::Null).await.ok(),
+        None => None,
+    };
+    match &live {
+        Some(live) => {
+            println!("Tracker:       running (pid {}, since {})", live["pid"], live["startedAt"].as_str().unwrap_or("?"));
+            if let Some(session) = live.get("session").filter(|s| !s.is_null()) {
+                println!(
+                    "Recording:     '{}' ({}) since {}",
+                    session["title"].as_str().unwrap_or(""),
+                    session_app(session),
+                    session["since"].as_str().unwrap_or("?")
+                );
+                if let Some(repository) = session["repository"].as_str() {
+                    match session["branch"].as_str() {
+                        Some(branch) => println!("Repository:    {} ({})", repository, branch),
+                        None => println!("Repository:    {}", repository),
+                    }
+                }
+                if let Some(category) = session["category"].as_str() {
+                    println!("Category:      {}", category);
+                }
+                if let Some(issues) = session["issues"].as_array() {
+                    let issues: Vec<&str> = issues.iter().filter_map(|v| v.as_str()).collect();
+                    println!("Issues:        {}", issues.join(", "));
+                }
+            }
+        }
+        None => match instance::running_instance() {
+            Some(owner) => println!("Tracker:       running (pid {}, since {}), control API not answering", owner.pid, owner.started_at),
+            None => println!("Tracker:       not running"),
+        },
+    }
+    let pause_state = PauseState::load();
+    let what = match pause_state.mode {
+        PauseMode::Paused => "paused",
+        PauseMode::Private => "private mode",
+    };
+    match (&pause_state.paused_since, &pause_state.paused_until) {
+        (Some(since), Some(until)) if pause_state.is_paused() => {
+            println!("Tracking:      {} since {}, resumes at {}", what, since, until)
+        }
+        (Some(since), None) => println!("Tracking:      {} since {} (run `chronos resume`)", what, since),
+        _ => println!("Tracking:      active"),
+    }
+    match &status.token_rejected_at {
+        Some(at) => println!("Sync:          paused, the server rejected the token at {} (run `chronos login`)", at),
+        None => println!("Sync:          active"),
+    }
+    println!("Last sync:     {}", status.last_sync.as_deref().unwrap_or("never"));
+    if let Some(error) = &status.last_error {
+        println!("Last error:    {}", error);
+    }
+}
+
+// The app identity when the app rules found one, else the process name
+fn session_app(session: &Value) -> &str {
+    session["app"].as_str().or_else(|| session["process"].as_str()).unwrap_or("")
+}
+
+// `chronos run` while a tracker is already running: show what it is doing
+// instead of starting a second one
+pub async fn report_running_instance(owner: &LockInfo) {
+    match owner.pid {
+        0 => println!("Chronos is already starting."),
+        pid => println!("Chronos is already running (pid {}, since {}).", pid, owner.started_at),
+    }
+    let live = match ipc::Client::connect().await {
+        Some(mut client) => client.call("status", Value::Null).await.ok(),
+        None => None,
+    };
+    if let Some(session) = live.as_ref().map(|live| &live["session"]).filter(|s| !s.is_null()) {
+        println!("Recording '{}' ({}).", session["title"].as_str().unwrap_or(""), session_app(session));
+    }
+    println!("Use `chronos status`, `chronos pause` or `chronos resume` to control it.");
+}
+
+// Pause and resume go through the running tracker so they apply at once;
+// without one the state file is all there is
+async fn pause(mode: PauseMode, until: &str) -> Result<(), Box<dyn Error>> {
+    let parsed = match until.trim() {
+        "" => None,
+        text => Some(
+            pause::parse_until(text, Local::now().naive_local())
+                .ok_or_else(|| format!("invalid pause end '{}', try 30m, until 14:00 or until tomorrow", text))?,
+        ),
+    };
+    let until = match ipc::Client::connect().await {
+        Some(mut client) => {
+            let params = json!({ "until": parsed.map(|_| until), "mode": mode });
+            let result = client.call("pause", params).await?;
+            result["pausedUntil"].as_str().map(str::to_string)
+        }
+        None => PauseState::pause(mode, parsed)?.paused_until,
+    };
+    let what = match mode {
+        PauseMode::Paused => "⏸️  Tracking paused",
+        PauseMode::Private => "🔒 Private mode on: time is recorded without titles or URLs",
+    };
+    match until {
+        Some(until) => println!("{} until {}. Run `chronos resume` to end it earlier.", what, until),
+        None => println!("{}. Run `chronos resume` to track normally again.", what),
+    }
+    Ok(())
+}
+
+async fn resume() -> Result<(), Box<dyn Error>> {
+    let was_paused = match ipc::Client::connect().await {
+        Some(mut client) => client.call("resume", Value::Null).await?["wasPaused"].as_bool().unwrap_or(true),
+        None => PauseState::resume()?,
+    };
+    if was_paused {
+        println!("▶️  Tracking resumed.");
+    } else {
+        println!("Tracking is not paused or private.");
+    }
+    Ok(())
+}
+
+async fn sync(config: &Config, server_url: &str, now: bool) -> Result<(), Box<dyn Error>> {
+    let mut status = SyncStatus::load();
+    if !now {
+        println!("The tracker syncs every 30 seconds. Last sync: {}", status.last_sync.as_deref().unwrap_or("never"));
+        println!("Run `chronos sync --now` to sync right away.");
+        return Ok(());
+    }
+
+    // The running tracker syncs with the stored token and updates its status
+    if let Some(mut client) = ipc::Client::connect().await {
+        client.call("sync", Value::Null).await?;
+        println!("✅ Synced.");
+        return Ok(());
+    }
+
+    let token = credentials::load_token().await.ok_or("not signed in, run `chronos login` first")?;
+    let privacy = Arc::new(privacy::Privacy::from_config(config));
+    let issues = issues::IssueDetector::from_config(config);
+    let categories = categories::Categoriser::from_config(config);
+    let policy = sync_policy::SyncPolicy::from_config(config);
+    let result = crate::sync_local_logs(&token, server_url, &privacy, &issues, &categories, &policy, &mut status).await;
+    match &result {
+        Ok(()) => {
+            status.last_sync = Some(sync_status::now());
+            status.last_error = None;
+            status.token_rejected_at = None;
+            println!("✅ Synced.");
+        }
+        Err(e) => {
+            status.last_error = Some(e.to_string());
+            if e.is::<crate::TokenRejected>() {
+                status.token_rejected_at = Some(sync_status::now());
+            }
+        }
+    }
+    status.save();
+    result.map_err(|e| {
+        if e.is::<crate::TokenRejected>() {
+            format!("{}, run `chronos login` to sign in again", e).into()
+        } else {
+            e
+        }
+    })
+}
+
+async fn print_events(limit: usize) -> Result<(), Box<dyn Error>> {
+    let mut client = ipc::Client::connect().await.ok_or("Chronos is not running")?;
+    let events = client.call("events", json!({ "limit": limit })).await?;
+    let events: Vec<LogEntry> = serde_json::from_value(events)?;
+    if events.is_empty() {
+        println!("Nothing recorded since the tracker started.");
+    }
+    for event in events {
+        let field = |key: &str| event.data.get(key).and_then(|v| v.as_str()).unwrap_or("").to_string();
+        let summary = match event.log_type.as_str() {
+            "window" => format!("{} ({})", field("windowTitle"), entry_app(&event)),
+            "browser" => format!("{} {}", field("browserTitle"), field("url")),
+            _ => field("event"),
+        };
+        println!("{}  {:<8} {}", event.timestamp, event.log_type, summary);
+    }
+    Ok(())
+}
+
+// The app identity of a window entry, else its process name
+fn entry_app(entry: &LogEntry) -> &str {
+    entry.data["app"].as_str().or_else(|| entry.data["processName"].as_str()).unwrap_or("")
+}
+
+fn in_days(entry: &LogEntry, since: Option<NaiveDate>, until: Option<NaiveDate>) -> bool {
+    let day = match entry.timestamp.get(..10).and_then(|d| NaiveDate::parse_from_str(d, "%Y-%m-%d").ok()) {
+        Some(day) => day,
+        None => return false,
+    };
+    since.is_none_or(|since| day >= since) && until.is_none_or(|until| day <= until)
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+fn export(
+    config: &Config,
+    format: ExportFormat,
+    since: Option<NaiveDate>,
+    until: Option<NaiveDate>,
+    output: Option<PathBuf>,
+) -> Result<(), Box<dyn Error>> {
+    let privacy = privacy::Privacy::from_config(config);
+    let mut entries = crate::read_log_entries(&privacy)?;
+    browser_time::annotate_browser_entries(&mut entries);
+    issues::IssueDetector::from_config(config).tag(&mut entries);
+    categories::Categoriser::from_config(config).tag(&mut entries);
+    let entries: Vec<LogEntry> = entries
+        .into_iter()
+        .filter(|e| e.log_type != "tracker")
+        .filter(|e| in_days(e, since, until))
+        .collect();
+
+    let text = match format {
+        ExportFormat::Json => serde_json::to_string_pretty(&entries)?,
+        ExportFormat::Csv => {
+            let mut text = String::from("timestamp,type,app,title,url,domain,seconds,issues\n");
+            for entry in &entries {
+                let field = |key: &str| entry.data.get(key).and_then(|v| v.as_str()).unwrap_or("").to_string();
+                let (app, title) = match entry.log_type.as_str() {
+                    "browser" => (field("browserType"), field("browserTitle")),
+                    _ => (entry_app(entry).to_string(), field("windowTitle")),
+                };
+                let seconds = ["focusSeconds", "durationSeconds"]
+                    .iter()
+                    .find_map(|key| entry.data.get(*key).and_then(|v| v.as_i64()))
+                    .map(|s| s.to_string())
+                    .unwrap_or_default();
+                let issues = entry.data.get("issues").and_then(|v| v.as_array()).into_iter().flatten().filter_map(|v| v.as_str());
+                let issues = issues.collect::<Vec<_>>().join(" ");
+                let row = [entry.timestamp.clone(), entry.log_type.clone(), app, title, field("url"), field("domain"), seconds, issues];
+                text.push_str(&row.iter().map(|v| csv_field(v)).collect::<Vec<_>>().join(","));
+                text.push('\n');
+            }
+            text
+        }
+    };
+
+    match output {
+        Some(path) => {
+            std::fs::write(&path, text)?;
+            eprintln!("Exported {} entries to {}", entries.len(), path.display());
+        }
+        None => std::io::stdout().write_all(text.as_bytes())?,
+    }
+    Ok(())
+}
+
+fn print_report(config: &Config, days: u32) -> Result<(), Box<dyn Error>> {
+    let days = days.max(1);
+    let privacy = privacy::Privacy::from_config(config);
+    let mut entries = crate::read_log_entries(&privacy)?;
+    issues::IssueDetector::from_config(config).tag(&mut entries);
+    categories::Categoriser::from_config(config).tag(&mut entries);
+
+    let now = Local::now().naive_local();
+    let first_day = now.date() - Duration::days(days as i64 - 1);
+    let from = first_day.and_hms_opt(0, 0, 0).unwrap();
+    let report = report::build(entries, from, now);
+
+    if days == 1 {
+        println!("Today ({})", first_day);
+    } else {
+        println!("Last {} days ({} to {})", days, first_day, now.date());
+    }
+    println!("Tracked: {}", report::format_duration(report.total_seconds()));
+    if let Some(score) = report.productivity {
+        println!("Productivity: {:+.1} (-2 distracting to +2 productive)", score);
+    }
+
+    if report.apps.is_empty() {
+        println!();
+        println!("No activity recorded.");
+        return Ok(());
+    }
+    println!();
+    println!("Apps");
+    for (app, seconds) in report.apps.iter().take(15) {
+        println!("  {:>8}  {}", report::format_duration(*seconds), app);
+    }
+    if !report.categories.is_empty() {
+        println!();
+        println!("Categories");
+        for (category, seconds) in &report.categories {
+            let depth = category.matches(" > ").count();
+            let name = category.rsplit(" > ").next().unwrap_or(category);
+            println!("  {:>8}  {}{}", report::format_duration(*seconds), "  ".repeat(depth), name);
+        }
+    }
+    if !report.domains.is_empty() {
+        println!();
+        println!("Websites");
+        for (domain, seconds) in report.domains.iter().take(15) {
+            println!("  {:>8}  {}", report::format_duration(*seconds), domain);
+        }
+    }
+    if !report.repositories.is_empty() {
+        println!();
+        println!("Repositories");
+        for (repository, seconds) in report.repositories.iter().take(15) {
+            println!("  {:>8}  {}", report::format_duration(*seconds), repository);
+        }
+    }
+    if !report.issues.is_empty() {
+        println!();
+        println!("Issues");
+        for (issue, seconds) in report.issues.iter().take(15) {
+            println!("  {:>8}  {}", report::format_duration(*seconds), issue);
+        }
+    }
+    Ok(())
+}
+
+fn config_command(config: &Config, action: ConfigAction) -> Result<(), Box<dyn Error>> {
+    match action {
+        ConfigAction::Show => println!("{}", serde_json::to_string_pretty(config)?),
+        ConfigAction::Path => println!("{}", config::config_path().display()),
+        ConfigAction::Get { key } => {
+            let value = config::get_value(config, &key).ok_or_else(|| format!("unknown setting '{}'", key))?;
+            match value {
+                serde_json::Value::String(s) => println!("{}", s),
+                other => println!("{}", serde_json::to_string_pretty(&other)?),
+            }
+        }
+        ConfigAction::Set { key, value } => {
+            let updated = config::set_value(config, &key, &value)?;
+            config::save_config(&updated)?;
+            println!("Set {}. Restart Chronos for the change to take effect.", key);
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,110 @@
+use serde::{Deserialize, Serialize};
+
+use crate::auth::DEFAULT_SERVER_URL;
+use crate::categories::CategoryConfig;
+use crate::editors::TitlePattern;
+use crate::issues::IssueConfig;
+use crate::privacy::ExclusionRule;
+use crate::process::AppRule;
+use crate::redact::RedactionConfig;
+use crate::sync_policy::SyncConfig;
+use crate::urls::UrlConfig;
+use crate::{get_app_data_dir, log_line};
+
+// -------------------- user configuration --------------------
+// Optional config.json in the data directory (%APPDATA%\Chronos,
+// ~/.local/share/chronos). Every field has a default so an empty or partial
+// file is fine; a missing file means all defaults.
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(default, rename_all = "camelCase")]
+pub struct Config {
+    // Dashboard/API base URL, for self-hosted servers
+    pub server_url: Option<String>,
+    pub urls: UrlConfig,
+    pub exclusions: Vec<ExclusionRule>,
+    // Checked before the built-in rules that name apps (see process)
+    pub app_rules: Vec<AppRule>,
+    // Checked before the built-in editor title parsers (see editors)
+    pub editor_titles: Vec<TitlePattern>,
+    pub issues: IssueConfig,
+    pub categories: CategoryConfig,
+    pub redaction: RedactionConfig,
+    pub sync: SyncConfig,
+}
+
+impl Config {
+    // CHRONOS_SERVER_URL wins over the config file, e.g. to point the client
+    // at a local stand-in server
+    pub fn server_url(&self) -> String {
+        std::env::var("CHRONOS_SERVER_URL")
+            .ok()
+            .filter(|url| !url.trim().is_empty())
+            .or_else(|| self.server_url.clone())
+            .unwrap_or_else(|| DEFAULT_SERVER_URL.to_string())
+            .trim_end_matches('/')
+            .to_string()
+    }
+}
+
+pub fn config_path() -> std::path::PathBuf {
+    get_app_data_dir().join("config.json")
+}
+
+pub fn load_config() -> Config {
+    let path = config_path();
+    let content = match std::fs::read_to_string(&path) {
+        Ok(content) => content,
+        Err(_) => return Config::default(),
+    };
+    match serde_json::from_str(&content) {
+        Ok(config) => config,
+        Err(e) => {
+            log_line(&format!("Invalid config file {}: {} (using defaults)", path.display(), e));
+            Config::default()
+        }
+    }
+}
+
+pub fn save_config(config: &Config) -> Result<(), Box<dyn std::error::Error>> {
+    std::fs::write(config_path(), serde_json::to_string_pretty(config)?)?;
+    Ok(())
+}
+
+// -------------------- `chronos config get/set` --------------------
+// Keys are dotted paths in the JSON form, e.g. "sync.level" or
+// "urls.domainOnly".
+
+pub fn get_value(config: &Config, key: &str) -> Option<serde_json::Value> {
+    let mut value = serde_json::to_value(config).ok()?;
+    for part in key.split('.') {
+        value = value.get_mut(part)?.take();
+    }
+    Some(value)
+}
+
+// `value` is parsed as JSON when it can be ("true", "3", "[...]") and taken
+// as a plain string otherwise
+pub fn set_value(config: &Config, key: &str, value: &str) -> Result<Config, Box<dyn std::error::Error>> {
+    let mut root = serde_json::to_value(config)?;
+    let known = root.as_object().is_some_and(|fields| fields.contains_key(key.split('.').next().unwrap_or("")));
+    if !known {
+        return Err(format!("unknown setting '{}'", key).into());
+    }
+
+    let new_value = serde_json::from_str(value).unwrap_or_else(|_| serde_json::Value::String(value.to_string()));
+    let mut slot = &mut root;
+    for part in key.split('.') {
+        if !slot.is_object() {
+            *slot = serde_json::Value::Object(serde_json::Map::new());
+        }
+        slot = slot
+            .as_object_mut()
+            .unwrap()
+            .entry(part.to_string())
+            .or_insert(serde_json::Value::Null);
+    }
+    *slot = new_value;
+
+    serde_json::from_value(root).map_err(|e| format!("invalid value for '{}': {}", key, e).into())
+}
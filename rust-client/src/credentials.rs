@@ -0,0 +1,440 @@
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+
+use crate::{get_app_data_dir, log_line};
+
+// -------------------- credential storage --------------------
+// The sync token is a bearer credential for the whole account, so it lives in
+// the OS credential store: Windows Credential Manager, or the Secret Service
+// (GNOME Keyring, KWallet) over D-Bus on Linux. Machines without either keep
+// it in an encrypted file readable by the owner only. Older versions wrote it
+// to sync_token.txt in plaintext; that file is moved into the store on startup.
+
+const SERVICE: &str = "Chronos";
+const ACCOUNT: &str = "sync-token";
+const PLAINTEXT_TOKEN_FILE: &str = "sync_token.txt";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Backend {
+    #[cfg(windows)]
+    CredentialManager,
+    #[cfg(target_os = "linux")]
+    SecretService,
+    EncryptedFile,
+}
+
+// Most preferred first
+const BACKENDS: &[Backend] = &[
+    #[cfg(windows)]
+    Backend::CredentialManager,
+    #[cfg(target_os = "linux")]
+    Backend::SecretService,
+    Backend::EncryptedFile,
+];
+
+impl Backend {
+    pub fn name(self) -> &'static str {
+        match self {
+            #[cfg(windows)]
+            Backend::CredentialManager => "Windows Credential Manager",
+            #[cfg(target_os = "linux")]
+            Backend::SecretService => "Secret Service keyring",
+            Backend::EncryptedFile => "encrypted token file",
+        }
+    }
+
+    // `dir` is the data directory, where the encrypted file lives
+    async fn load(self, dir: &Path) -> Result<Option<String>, Box<dyn Error>> {
+        match self {
+            #[cfg(windows)]
+            Backend::CredentialManager => credential_manager::load(),
+            #[cfg(target_os = "linux")]
+            Backend::SecretService => keyring::load().await,
+            Backend::EncryptedFile => encrypted_file::load(dir),
+        }
+    }
+
+    async fn store(self, dir: &Path, token: &str) -> Result<(), Box<dyn Error>> {
+        match self {
+            #[cfg(windows)]
+            Backend::CredentialManager => credential_manager::store(token),
+            #[cfg(target_os = "linux")]
+            Backend::SecretService => keyring::store(token).await,
+            Backend::EncryptedFile => encrypted_file::store(dir, token),
+        }
+    }
+
+    async fn delete(self, dir: &Path) -> Result<(), Box<dyn Error>> {
+        match self {
+            #[cfg(windows)]
+            Backend::CredentialManager => credential_manager::delete(),
+            #[cfg(target_os = "linux")]
+            Backend::SecretService => keyring::delete().await,
+            Backend::EncryptedFile => encrypted_file::delete(dir),
+        }
+    }
+}
+
+pub async fn load_token() -> Option<String> {
+    load_token_from(&get_app_data_dir(), BACKENDS).await
+}
+
+async fn load_token_from(dir: &Path, backends: &[Backend]) -> Option<String> {
+    for backend in backends {
+        match backend.load(dir).await {
+            Ok(Some(token)) if !token.trim().is_empty() => return Some(token.trim().to_string()),
+            Ok(_) => {}
+            Err(e) => log_line(&format!("Could not read token from {}: {}", backend.name(), e)),
+        }
+    }
+    None
+}
+
+// Store the token in the most preferred store that works and remove copies
+// from the others, so a stale token can't shadow the new one
+pub async fn save_token(token: &str) -> Result<Backend, Box<dyn Error>> {
+    save_token_in(&get_app_data_dir(), BACKENDS, token).await
+}
+
+async fn save_token_in(dir: &Path, backends: &[Backend], token: &str) -> Result<Backend, Box<dyn Error>> {
+    // Kept as text so the future stays Send for the sync task
+    let mut last_error = String::from("no credential store available");
+    for backend in backends {
+        if let Err(e) = backend.store(dir, token).await {
+            log_line(&format!("Could not store token in {}: {}", backend.name(), e));
+            last_error = e.to_string();
+            continue;
+        }
+        for other in backends.iter().filter(|b| *b != backend) {
+            let _ = other.delete(dir).await;
+        }
+        return Ok(*backend);
+    }
+    Err(last_error.into())
+}
+
+// Remove the token from every store (`chronos logout`)
+pub async fn delete_token() -> Result<(), Box<dyn Error>> {
+    let dir = get_app_data_dir();
+    let mut failed = Vec::new();
+    for backend in BACKENDS {
+        if let Err(e) = backend.delete(&dir).await {
+            failed.push(format!("{}: {}", backend.name(), e));
+        }
+    }
+    if failed.is_empty() {
+        Ok(())
+    } else {
+        Err(failed.join("; ").into())
+    }
+}
+
+// Move a token left in sync_token.txt by older versions into the store
+pub async fn migrate_plaintext_token() {
+    migrate_plaintext_token_in(&get_app_data_dir(), BACKENDS).await
+}
+
+async fn migrate_plaintext_token_in(dir: &Path, backends: &[Backend]) {
+    let path = dir.join(PLAINTEXT_TOKEN_FILE);
+    let token = match fs::read_to_string(&path) {
+        Ok(token) => token.trim().to_string(),
+        Err(_) => return,
+    };
+
+    // A token already in the store came from a newer sign-in
+    if !token.is_empty() && load_token_from(dir, backends).await.is_none() {
+        match save_token_in(dir, backends, &token).await {
+            Ok(backend) => log_line(&format!("Moved sync token from {} to the {}", PLAINTEXT_TOKEN_FILE, backend.name())),
+            Err(e) => {
+                log_line(&format!("Keeping {} for now, could not store the token: {}", PLAINTEXT_TOKEN_FILE, e));
+                return;
+            }
+        }
+    }
+
+    if let Err(e) = fs::remove_file(&path) {
+        log_line(&format!("Could not remove {}: {}", path.display(), e));
+    }
+}
+
+#[cfg(windows)]
+mod credential_manager {
+    use std::error::Error;
+    use windows::core::{HRESULT, HSTRING, PWSTR};
+    use windows::Win32::Foundation::ERROR_NOT_FOUND;
+    use windows::Win32::Security::Credentials::{
+        CredDeleteW, CredFree, CredReadW, CredWriteW, CREDENTIALW, CRED_PERSIST_LOCAL_MACHINE, CRED_TYPE_GENERIC,
+    };
+
+    use super::{ACCOUNT, SERVICE};
+
+    fn target() -> String {
+        format!("{}/{}", SERVICE, ACCOUNT)
+    }
+
+    fn is_not_found(e: &windows::core::Error) -> bool {
+        e.code() == HRESULT::from_win32(ERROR_NOT_FOUND.0)
+    }
+
+    pub fn load() -> Result<Option<String>, Box<dyn Error>> {
+        let target = HSTRING::from(target());
+        let mut credential: *mut CREDENTIALW = std::ptr::null_mut();
+        unsafe {
+            match CredReadW(&target, CRED_TYPE_GENERIC, 0, &mut credential) {
+                Ok(()) => {}
+                Err(e) if is_not_found(&e) => return Ok(None),
+                Err(e) => return Err(e.into()),
+            }
+            let blob = std::slice::from_raw_parts((*credential).CredentialBlob, (*credential).CredentialBlobSize as usize);
+            let token = String::from_utf8(blob.to_vec());
+            CredFree(credential as *const _);
+            Ok(Some(token?))
+        }
+    }
+
+    pub fn store(token: &str) -> Result<(), Box<dyn Error>> {
+        let mut target: Vec<u16> = target().encode_utf16().chain(Some(0)).collect();
+        let mut user: Vec<u16> = ACCOUNT.encode_utf16().chain(Some(0)).collect();
+        let mut blob = token.as_bytes().to_vec();
+        let credential = CREDENTIALW {
+            Type: CRED_TYPE_GENERIC,
+            TargetName: PWSTR(target.as_mut_ptr()),
+            UserName: PWSTR(user.as_mut_ptr()),
+            CredentialBlobSize: blob.len() as u32,
+            CredentialBlob: blob.as_mut_ptr(),
+            Persist: CRED_PERSIST_LOCAL_MACHINE,
+            ..Default::default()
+        };
+        unsafe { CredWriteW(&credential, 0)? };
+        Ok(())
+    }
+
+    pub fn delete() -> Result<(), Box<dyn Error>> {
+        match unsafe { CredDeleteW(&HSTRING::from(target()), CRED_TYPE_GENERIC, 0) } {
+            Err(e) if !is_not_found(&e) => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod keyring {
+    use secret_service::{EncryptionType, SecretService};
+    use std::collections::HashMap;
+    use std::error::Error;
+
+    use super::{ACCOUNT, SERVICE};
+
+    fn attributes() -> HashMap<&'static str, &'static str> {
+        HashMap::from([("service", SERVICE), ("account", ACCOUNT)])
+    }
+
+    pub async fn load() -> Result<Option<String>, Box<dyn Error>> {
+        // No keyring on this machine (headless, no session bus): nothing stored there
+        let service = match SecretService::connect(EncryptionType::Dh).await {
+            Err(secret_service::Error::Unavailable) => return Ok(None),
+            service => service?,
+        };
+        let found = service.search_items(attributes()).await?;
+        let item = match found.unlocked.first().or(found.locked.first()) {
+            Some(item) => item,
+            None => return Ok(None),
+        };
+        // May show the keyring's unlock prompt
+        item.ensure_unlocked().await?;
+        Ok(Some(String::from_utf8(item.get_secret().await?)?))
+    }
+
+    pub async fn store(token: &str) -> Result<(), Box<dyn Error>> {
+        let service = SecretService::connect(EncryptionType::Dh).await?;
+        let collection = service.get_default_collection().await?;
+        collection.ensure_unlocked().await?;
+        collection
+            .create_item("Chronos sync token", attributes(), token.as_bytes(), true, "text/plain")
+            .await?;
+        Ok(())
+    }
+
+    pub async fn delete() -> Result<(), Box<dyn Error>> {
+        let service = match SecretService::connect(EncryptionType::Dh).await {
+            Err(secret_service::Error::Unavailable) => return Ok(()),
+            service => service?,
+        };
+        let found = service.search_items(attributes()).await?;
+        for item in found.unlocked.iter().chain(&found.locked) {
+            item.delete().await?;
+        }
+        Ok(())
+    }
+}
+
+// Fallback for machines without a keyring. The key sits next to the token,
+// so this keeps the token out of backups, sync folders and grep rather than
+// away from someone who can already read the user's files.
+mod encrypted_file {
+    use chacha20poly1305::aead::{Aead, KeyInit};
+    use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+    use std::error::Error;
+    use std::fs::{self, OpenOptions};
+    use std::io::Write;
+    use std::path::Path;
+
+    pub const TOKEN_FILE: &str = "token.enc";
+    pub const KEY_FILE: &str = "token.key";
+    const NONCE_LEN: usize = 12;
+
+    // Owner-only (0600) on Unix; %APPDATA% is already private to the user on Windows
+    fn write_private(path: &Path, bytes: &[u8]) -> std::io::Result<()> {
+        let mut options = OpenOptions::new();
+        options.write(true).create(true).truncate(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            options.mode(0o600);
+        }
+        let mut file = options.open(path)?;
+        // mode() only applies when the file is created
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            file.set_permissions(fs::Permissions::from_mode(0o600))?;
+        }
+        file.write_all(bytes)
+    }
+
+    fn cipher(key: &[u8]) -> Result<ChaCha20Poly1305, Box<dyn Error>> {
+        if key.len() != 32 {
+            return Err(format!("{} is corrupt", KEY_FILE).into());
+        }
+        Ok(ChaCha20Poly1305::new(Key::from_slice(key)))
+    }
+
+    pub fn load(dir: &Path) -> Result<Option<String>, Box<dyn Error>> {
+        let sealed = match fs::read(dir.join(TOKEN_FILE)) {
+            Ok(sealed) => sealed,
+            Err(_) => return Ok(None),
+        };
+        if sealed.len() <= NONCE_LEN {
+            return Err(format!("{} is corrupt", TOKEN_FILE).into());
+        }
+        let key = fs::read(dir.join(KEY_FILE)).map_err(|e| format!("{}: {}", KEY_FILE, e))?;
+        let (nonce, ciphertext) = sealed.split_at(NONCE_LEN);
+        let token = cipher(&key)?
+            .decrypt(Nonce::from_slice(nonce), ciphertext)
+            .map_err(|_| format!("could not decrypt {}", TOKEN_FILE))?;
+        Ok(Some(String::from_utf8(token)?))
+    }
+
+    pub fn store(dir: &Path, token: &str) -> Result<(), Box<dyn Error>> {
+        let key_path = dir.join(KEY_FILE);
+        let key = match fs::read(&key_path) {
+            Ok(key) if key.len() == 32 => key,
+            _ => {
+                let mut key = vec![0u8; 32];
+                getrandom::getrandom(&mut key).map_err(|e| e.to_string())?;
+                write_private(&key_path, &key)?;
+                key
+            }
+        };
+
+        let mut nonce = [0u8; NONCE_LEN];
+        getrandom::getrandom(&mut nonce).map_err(|e| e.to_string())?;
+        let ciphertext = cipher(&key)?
+            .encrypt(Nonce::from_slice(&nonce), token.as_bytes())
+            .map_err(|_| "could not encrypt the token")?;
+
+        let mut sealed = nonce.to_vec();
+        sealed.extend_from_slice(&ciphertext);
+        write_private(&dir.join(TOKEN_FILE), &sealed)?;
+        Ok(())
+    }
+
+    pub fn delete(dir: &Path) -> Result<(), Box<dyn Error>> {
+        for name in [TOKEN_FILE, KEY_FILE] {
+            match fs::remove_file(dir.join(name)) {
+                Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e.into()),
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("chronos-credentials-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    const FILE: &[Backend] = &[Backend::EncryptedFile];
+
+    #[tokio::test]
+    async fn encrypted_file_round_trip() {
+        let dir = temp_dir("round-trip");
+        assert_eq!(load_token_from(&dir, FILE).await, None);
+
+        assert_eq!(save_token_in(&dir, FILE, "header.payload.sig").await.unwrap(), Backend::EncryptedFile);
+        let sealed = fs::read(dir.join(encrypted_file::TOKEN_FILE)).unwrap();
+        assert!(!String::from_utf8_lossy(&sealed).contains("payload"));
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            for name in [encrypted_file::TOKEN_FILE, encrypted_file::KEY_FILE] {
+                assert_eq!(fs::metadata(dir.join(name)).unwrap().permissions().mode() & 0o777, 0o600, "{}", name);
+            }
+        }
+        assert_eq!(load_token_from(&dir, FILE).await.as_deref(), Some("header.payload.sig"));
+
+        // a new token keeps the key
+        let key = fs::read(dir.join(encrypted_file::KEY_FILE)).unwrap();
+        save_token_in(&dir, FILE, "second.token.sig").await.unwrap();
+        assert_eq!(fs::read(dir.join(encrypted_file::KEY_FILE)).unwrap(), key);
+        assert_eq!(load_token_from(&dir, FILE).await.as_deref(), Some("second.token.sig"));
+
+        // a token sealed with another key doesn't decrypt
+        fs::write(dir.join(encrypted_file::KEY_FILE), [7u8; 32]).unwrap();
+        assert!(encrypted_file::load(&dir).is_err());
+        assert_eq!(load_token_from(&dir, FILE).await, None);
+
+        encrypted_file::delete(&dir).unwrap();
+        assert!(!dir.join(encrypted_file::TOKEN_FILE).exists() && !dir.join(encrypted_file::KEY_FILE).exists());
+        encrypted_file::delete(&dir).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn plaintext_token_migration() {
+        let dir = temp_dir("migration");
+        let plaintext = dir.join(PLAINTEXT_TOKEN_FILE);
+        // nothing to move
+        migrate_plaintext_token_in(&dir, FILE).await;
+        assert_eq!(load_token_from(&dir, FILE).await, None);
+
+        fs::write(&plaintext, "  old.token.sig\n").unwrap();
+        migrate_plaintext_token_in(&dir, FILE).await;
+        assert_eq!(load_token_from(&dir, FILE).await.as_deref(), Some("old.token.sig"));
+        assert!(!plaintext.exists());
+
+        // a token from a newer sign-in stays
+        save_token_in(&dir, FILE, "new.token.sig").await.unwrap();
+        fs::write(&plaintext, "old.token.sig").unwrap();
+        migrate_plaintext_token_in(&dir, FILE).await;
+        assert_eq!(load_token_from(&dir, FILE).await.as_deref(), Some("new.token.sig"));
+        assert!(!plaintext.exists());
+
+        // an empty file is just removed
+        encrypted_file::delete(&dir).unwrap();
+        fs::write(&plaintext, "\n").unwrap();
+        migrate_plaintext_token_in(&dir, FILE).await;
+        assert_eq!(load_token_from(&dir, FILE).await, None);
+        assert!(!plaintext.exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
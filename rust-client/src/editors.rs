@@ -0,0 +1,352 @@
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+use crate::config::Config;
+use crate::log_line;
+use crate::process::{jetbrains_names, process_name, ProcessInfo, JETBRAINS_PRODUCTS};
+
+// -------------------- editor titles --------------------
+// Editors put the project and the open file in the window title. Parsers
+// keyed by process name pull them out, so time can be counted per project
+// and language rather than per editor. The editor is recognised by its
+// process name, its app identity (see process) or, for terminal editors,
+// the terminal's foreground command. Patterns from the "editorTitles"
+// setting are tried before the built-in parsers.
+
+// e.g. { "process": "sublime_text", "pattern": "^(?P<file>.+?) \\((?P<project>[^)]+)\\) - Sublime Text$" }
+// Named groups "project", "file" and "language" are used; the language
+// otherwise follows from the file's extension.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(default, rename_all = "camelCase")]
+pub struct TitlePattern {
+    // Process name, case-insensitive, ".exe" optional; any window when unset
+    pub process: Option<String>,
+    pub pattern: String,
+}
+
+#[derive(Debug, Default)]
+struct EditorTitle {
+    project: Option<String>,
+    file: Option<String>,
+}
+
+pub const VS_CODE: &[&str] = &["code", "code-insiders", "code-oss", "codium", "vscodium", "cursor", "windsurf"];
+pub const VIM: &[&str] = &["vim", "nvim", "gvim", "vi"];
+
+// By file extension, lowercase
+const LANGUAGES: &[(&[&str], &str)] = &[
+    (&["rs"], "Rust"),
+    (&["py", "pyi"], "Python"),
+    (&["ts", "tsx", "mts", "cts"], "TypeScript"),
+    (&["js", "jsx", "mjs", "cjs"], "JavaScript"),
+    (&["go"], "Go"),
+    (&["java"], "Java"),
+    (&["kt", "kts"], "Kotlin"),
+    (&["scala"], "Scala"),
+    (&["c", "h"], "C"),
+    (&["cpp", "cc", "cxx", "hpp", "hh", "hxx"], "C++"),
+    (&["cs"], "C#"),
+    (&["swift"], "Swift"),
+    (&["rb"], "Ruby"),
+    (&["php"], "PHP"),
+    (&["dart"], "Dart"),
+    (&["lua"], "Lua"),
+    (&["ex", "exs"], "Elixir"),
+    (&["hs"], "Haskell"),
+    (&["zig"], "Zig"),
+    (&["sh", "bash", "zsh", "fish"], "Shell"),
+    (&["ps1"], "PowerShell"),
+    (&["sql"], "SQL"),
+    (&["html", "htm"], "HTML"),
+    (&["css", "scss", "sass", "less"], "CSS"),
+    (&["vue"], "Vue"),
+    (&["svelte"], "Svelte"),
+    (&["md", "markdown"], "Markdown"),
+    (&["json", "jsonc"], "JSON"),
+    (&["yaml", "yml"], "YAML"),
+    (&["toml"], "TOML"),
+    (&["xml"], "XML"),
+];
+
+fn language_of(file: &str) -> Option<String> {
+    let extension = Path::new(file).extension()?.to_string_lossy().to_lowercase();
+    LANGUAGES
+        .iter()
+        .find(|(extensions, _)| extensions.contains(&extension.as_str()))
+        .map(|(_, language)| language.to_string())
+}
+
+// "Code.exe" -> "code", "idea64.exe" -> "idea"
+fn normalise(name: &str) -> String {
+    let name = process_name(name);
+    name.strip_suffix("64").unwrap_or(&name).to_string()
+}
+
+fn non_empty(text: &str) -> Option<String> {
+    Some(text.trim().to_string()).filter(|text| !text.is_empty())
+}
+
+// Drop a trailing " [...]": VS Code's remote name, JetBrains' project path
+// or module
+fn strip_brackets(text: &str) -> &str {
+    match text.trim_end().strip_suffix(']').and_then(|rest| rest.rfind(" [")) {
+        Some(start) => &text[..start],
+        None => text,
+    }
+}
+
+// "● main.rs - chronos - Visual Studio Code", "main.rs - chronos [SSH: box] - Cursor",
+// "chronos - Visual Studio Code" (no file open), "main.rs - Visual Studio Code"
+// (no folder open)
+fn parse_vs_code(title: &str) -> Option<EditorTitle> {
+    let title = title.trim_start_matches(['●', '•', ' ']);
+    let title = title.strip_suffix(" - Insiders").unwrap_or(title);
+    let (rest, _app) = title.rsplit_once(" - ")?;
+    let parts: Vec<&str> = strip_brackets(rest).split(" - ").collect();
+    match parts.as_slice() {
+        [single] if Path::new(single).extension().is_some() => Some(EditorTitle { file: non_empty(single), project: None }),
+        [single] => Some(EditorTitle { project: non_empty(single), file: None }),
+        [file, .., project] => Some(EditorTitle { project: non_empty(project), file: non_empty(file) }),
+        [] => None,
+    }
+}
+
+// "chronos – main.rs", "chronos – main.rs [chronos] - IntelliJ IDEA" and the
+// older "chronos [~/src/chronos] – .../src/main.rs [chronos] - IntelliJ IDEA";
+// the separator is an en dash
+fn parse_jetbrains(title: &str) -> Option<EditorTitle> {
+    let mut title = title;
+    if let Some((rest, product)) = title.rsplit_once(" - ") {
+        let product = product.to_lowercase();
+        let is_product = |name: &str| product.starts_with(&name.to_lowercase());
+        if JETBRAINS_PRODUCTS.iter().any(|(_, name)| is_product(name)) {
+            title = rest;
+        }
+    }
+    let mut parts = title.split(" – ");
+    let project = non_empty(strip_brackets(parts.next()?));
+    let file = parts.last().map(strip_brackets).and_then(|file| non_empty(file.rsplit(['/', '\\']).next().unwrap_or(file)));
+    Some(EditorTitle { project, file })
+}
+
+// "main.rs (~/src/chronos) - VIM", "main.rs + (~/src) - GVIM1" (modified),
+// "main.rs (~/src/chronos) - Nvim"; the project is the directory's name
+fn parse_vim(title: &str) -> Option<EditorTitle> {
+    let (rest, app) = title.rsplit_once(" - ")?;
+    if !app.to_lowercase().contains("vim") {
+        return None;
+    }
+    let (file, dir) = match rest.rsplit_once(" (") {
+        Some((file, dir)) => (file, dir.strip_suffix(')')),
+        None => (rest, None),
+    };
+    let project = dir
+        .and_then(|dir| dir.trim_end_matches(['/', '\\']).rsplit(['/', '\\']).next())
+        .filter(|name| *name != "~")
+        .and_then(non_empty);
+    // flags after the name: + modified, - not modifiable, = read-only;
+    // "[No Name]" is a buffer without a file
+    let file = non_empty(file.trim_end_matches([' ', '+', '-', '='])).filter(|file| file != "[No Name]");
+    Some(EditorTitle { project, file })
+}
+
+struct BuiltIn {
+    names: Vec<String>,
+    parse: fn(&str) -> Option<EditorTitle>,
+}
+
+struct CompiledPattern {
+    process: Option<String>,
+    pattern: Regex,
+}
+
+pub struct TitleParsers {
+    patterns: Vec<CompiledPattern>,
+    built_in: Vec<BuiltIn>,
+}
+
+impl TitleParsers {
+    pub fn from_config(config: &Config) -> TitleParsers {
+        let patterns = config
+            .editor_titles
+            .iter()
+            .filter_map(|rule| match Regex::new(&rule.pattern) {
+                Ok(pattern) => Some(CompiledPattern { process: rule.process.as_deref().map(normalise), pattern }),
+                Err(e) => {
+                    log_line(&format!("Ignoring editor title pattern '{}': {}", rule.pattern, e));
+                    None
+                }
+            })
+            .collect();
+        let names = |names: &[&str]| names.iter().map(|name| name.to_string()).collect();
+        let built_in = vec![
+            BuiltIn { names: names(VS_CODE), parse: parse_vs_code },
+            BuiltIn { names: jetbrains_names(), parse: parse_jetbrains },
+            BuiltIn { names: names(VIM), parse: parse_vim },
+        ];
+        TitleParsers { patterns, built_in }
+    }
+
+    // Fill in the project, file and language the editor's title shows
+    pub fn parse(&self, process: &str, title: &str, mut info: ProcessInfo) -> ProcessInfo {
+        // the names this window goes by
+        let foreground = info.foreground_command.as_deref().and_then(|command| command.split_whitespace().next());
+        let names: Vec<String> = [Some(process), info.app.as_deref(), foreground.map(|program| program.rsplit('/').next().unwrap_or(program))]
+            .into_iter()
+            .flatten()
+            .map(normalise)
+            .collect();
+
+        let mut language = None;
+        let from_pattern = self
+            .patterns
+            .iter()
+            .filter(|rule| rule.process.as_ref().is_none_or(|wanted| names.contains(wanted)))
+            .find_map(|rule| {
+                let captures = rule.pattern.captures(title)?;
+                let group = |name: &str| captures.name(name).and_then(|text| non_empty(text.as_str()));
+                language = group("language");
+                Some(EditorTitle { project: group("project"), file: group("file") })
+            });
+        let parsed = from_pattern.or_else(|| {
+            self.built_in
+                .iter()
+                .filter(|parser| parser.names.iter().any(|name| names.contains(name)))
+                .find_map(|parser| (parser.parse)(title))
+        });
+
+        if let Some(parsed) = parsed {
+            info.language = language.or_else(|| parsed.file.as_deref().and_then(language_of));
+            info.project = parsed.project;
+            info.file = parsed.file;
+        }
+        info
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // (title, project, file), as the editors write them
+    type Fixture = (&'static str, Option<&'static str>, Option<&'static str>);
+
+    fn check(parse: fn(&str) -> Option<EditorTitle>, fixtures: &[Fixture]) {
+        for (title, project, file) in fixtures {
+            let parsed = parse(title).unwrap_or_else(|| panic!("no match for {:?}", title));
+            assert_eq!((parsed.project.as_deref(), parsed.file.as_deref()), (*project, *file), "{:?}", title);
+        }
+    }
+
+    #[test]
+    fn vs_code_titles() {
+        check(
+            parse_vs_code,
+            &[
+                ("main.rs - chronos - Visual Studio Code", Some("chronos"), Some("main.rs")),
+                // unsaved changes
+                ("● main.rs - chronos - Visual Studio Code", Some("chronos"), Some("main.rs")),
+                ("main.rs - chronos - Visual Studio Code - Insiders", Some("chronos"), Some("main.rs")),
+                ("● lib.rs - chronos - Visual Studio Code - Insiders", Some("chronos"), Some("lib.rs")),
+                ("main.rs - chronos [SSH: devbox] - Visual Studio Code", Some("chronos"), Some("main.rs")),
+                ("app.py - api [WSL: Ubuntu-22.04] - Visual Studio Code", Some("api"), Some("app.py")),
+                ("index.ts - web [Dev Container: Node.js] - Cursor", Some("web"), Some("index.ts")),
+                // no file open, no folder open
+                ("chronos - Visual Studio Code", Some("chronos"), None),
+                ("chronos [SSH: devbox] - Visual Studio Code", Some("chronos"), None),
+                ("notes.md - Visual Studio Code", None, Some("notes.md")),
+                ("Cargo.toml - chronos - VSCodium", Some("chronos"), Some("Cargo.toml")),
+            ],
+        );
+    }
+
+    #[test]
+    fn jetbrains_titles() {
+        check(
+            parse_jetbrains,
+            &[
+                // 2023.2 and later
+                ("chronos – main.rs", Some("chronos"), Some("main.rs")),
+                ("api – views.py", Some("api"), Some("views.py")),
+                ("chronos – main.rs [chronos] - RustRover", Some("chronos"), Some("main.rs")),
+                ("chronos [~/src/chronos] – .../src/main.rs [chronos] - IntelliJ IDEA", Some("chronos"), Some("main.rs")),
+                ("api [C:\\src\\api] – ...\\api\\views.py - PyCharm", Some("api"), Some("views.py")),
+                // no file open
+                ("chronos", Some("chronos"), None),
+                ("chronos [~/src/chronos] - IntelliJ IDEA", Some("chronos"), None),
+            ],
+        );
+    }
+
+    #[test]
+    fn vim_titles() {
+        check(
+            parse_vim,
+            &[
+                ("main.rs (~/src/chronos) - VIM", Some("chronos"), Some("main.rs")),
+                ("main.rs (~/src/chronos/src) - NVIM", Some("src"), Some("main.rs")),
+                ("main.rs (~/src/chronos) - Nvim", Some("chronos"), Some("main.rs")),
+                // modified, read-only, both
+                ("main.rs + (~/src/chronos) - VIM", Some("chronos"), Some("main.rs")),
+                ("main.rs = (/etc) - VIM", Some("etc"), Some("main.rs")),
+                ("main.rs =+ (~/src/chronos) - GVIM1", Some("chronos"), Some("main.rs")),
+                // files in the home directory, no file
+                (".bashrc (~) - VIM", None, Some(".bashrc")),
+                ("[No Name] - NVIM", None, None),
+                ("[No Name] + - VIM", None, None),
+            ],
+        );
+        assert!(parse_vim("main.rs - chronos - Visual Studio Code").is_none());
+    }
+
+    fn parse(config: &Config, process: &str, title: &str, info: ProcessInfo) -> (Option<String>, Option<String>, Option<String>) {
+        let info = TitleParsers::from_config(config).parse(process, title, info);
+        (info.project, info.file, info.language)
+    }
+
+    fn some(text: &str) -> Option<String> {
+        Some(text.to_string())
+    }
+
+    #[test]
+    fn parsers_by_name() {
+        let config = Config::default();
+        assert_eq!(
+            parse(&config, "Code.exe", "● main.rs - chronos - Visual Studio Code", ProcessInfo::default()),
+            (some("chronos"), some("main.rs"), some("Rust"))
+        );
+        assert_eq!(parse(&config, "idea64.exe", "chronos – Main.kt", ProcessInfo::default()), (some("chronos"), some("Main.kt"), some("Kotlin")));
+        // a java-launched IDE by its app identity, vim by the terminal's foreground command
+        let pycharm = ProcessInfo { app: some("PyCharm"), ..Default::default() };
+        assert_eq!(parse(&config, "java", "api – views.py", pycharm), (some("api"), some("views.py"), some("Python")));
+        let nvim = ProcessInfo { foreground_command: some("/usr/bin/nvim src/main.rs"), ..Default::default() };
+        assert_eq!(parse(&config, "kitty", "main.rs + (~/src/chronos) - NVIM", nvim), (some("chronos"), some("main.rs"), some("Rust")));
+        // other apps' titles are left alone
+        assert_eq!(parse(&config, "firefox", "main.rs - chronos - Mozilla Firefox", ProcessInfo::default()), (None, None, None));
+    }
+
+    #[test]
+    fn user_patterns() {
+        let pattern = |process: Option<&str>, pattern: &str| TitlePattern { process: process.map(str::to_string), pattern: pattern.to_string() };
+        let config = Config {
+            editor_titles: vec![
+                pattern(Some("Sublime_Text.exe"), r"^(?P<file>.+?) \((?P<project>[^)]+)\) - Sublime Text$"),
+                // before the built-in VS Code parser
+                pattern(Some("code"), r"^\[(?P<project>[^\]]+)\] (?P<file>\S+) - Visual Studio Code$"),
+                pattern(None, r"^(?P<file>\S+) \[(?P<language>[^\]]+)\] - Helix$"),
+                pattern(None, "(unclosed"),
+            ],
+            ..Default::default()
+        };
+        assert_eq!(
+            parse(&config, "sublime_text.exe", "main.rs (chronos) - Sublime Text", ProcessInfo::default()),
+            (some("chronos"), some("main.rs"), some("Rust"))
+        );
+        assert_eq!(parse(&config, "code", "[chronos] main.rs - Visual Studio Code", ProcessInfo::default()), (some("chronos"), some("main.rs"), some("Rust")));
+        assert_eq!(parse(&config, "code", "main.rs - chronos - Visual Studio Code", ProcessInfo::default()), (some("chronos"), some("main.rs"), some("Rust")));
+        assert_eq!(parse(&config, "hx", "build.zig [Zig] - Helix", ProcessInfo::default()), (None, some("build.zig"), some("Zig")));
+        // a pattern for another process doesn't apply
+        assert_eq!(parse(&config, "gedit", "main.rs (chronos) - Sublime Text", ProcessInfo::default()), (None, None, None));
+    }
+}
@@ -0,0 +1,45 @@
+use chrono::NaiveDateTime;
+
+use crate::LogEntry;
+
+// -------------------- log entries --------------------
+// Reading back what parse_log_line made of the activity log, shared by the
+// browser time, sync and report code.
+
+// Log lines carry milliseconds; older ones don't
+const TIMESTAMP_FORMAT: &str = "%Y-%m-%d %H:%M:%S%.f";
+
+pub fn parse_timestamp(text: &str) -> Option<NaiveDateTime> {
+    NaiveDateTime::parse_from_str(text, TIMESTAMP_FORMAT).ok()
+}
+
+pub fn format_timestamp(t: NaiveDateTime) -> String {
+    t.format(TIMESTAMP_FORMAT).to_string()
+}
+
+pub fn entry_time(entry: &LogEntry) -> Option<NaiveDateTime> {
+    parse_timestamp(&entry.timestamp)
+}
+
+// A text field of the entry's data, "" when missing
+pub fn data_str<'a>(entry: &'a LogEntry, key: &str) -> &'a str {
+    entry.data.get(key).and_then(|v| v.as_str()).unwrap_or("")
+}
+
+// (entry index, start, end) for each window entry. A session runs until the
+// next window entry or tracker event (pause, stop); the last one is still
+// open and left out.
+pub fn window_sessions(entries: &[LogEntry]) -> Vec<(usize, NaiveDateTime, NaiveDateTime)> {
+    let mut boundaries: Vec<(usize, NaiveDateTime)> = entries
+        .iter()
+        .enumerate()
+        .filter(|(_, e)| e.log_type == "window" || e.log_type == "tracker")
+        .filter_map(|(i, e)| entry_time(e).map(|t| (i, t)))
+        .collect();
+    boundaries.sort_by_key(|(_, t)| *t);
+    boundaries
+        .windows(2)
+        .filter(|pair| entries[pair[0].0].log_type == "window")
+        .map(|pair| (pair[0].0, pair[0].1, pair[1].1))
+        .collect()
+}
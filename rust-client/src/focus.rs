@@ -0,0 +1,276 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+use tokio::sync::Notify;
+
+use crate::log_line;
+
+// -------------------- focus changes --------------------
+// Event sources report focus changes as they happen and wake the main loop,
+// so short switches are recorded and timed to the millisecond: a WinEvent
+// hook on Windows, PropertyNotify on _NET_ACTIVE_WINDOW under X11 and the
+// compositor on Wayland (see wayland). Without one, the main loop falls back
+// to polling the foreground window on every pass.
+
+// The focused window as last reported
+static FOCUSED: Mutex<Option<Window>> = Mutex::new(None);
+// Whether an event source is running; otherwise active_window() polls
+static EVENTS: AtomicBool = AtomicBool::new(false);
+static WAKE: OnceLock<Arc<Notify>> = OnceLock::new();
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Window {
+    pub title: String,
+    // Executable name, or what the window system calls the app
+    pub process: String,
+    // Unknown e.g. for windows of other users' processes
+    pub pid: Option<u32>,
+}
+
+pub fn set_focused(window: Option<Window>) {
+    let mut focused = FOCUSED.lock().unwrap();
+    if *focused != window {
+        *focused = window;
+        if let Some(wake) = WAKE.get() {
+            wake.notify_one();
+        }
+    }
+}
+
+pub fn active_window() -> Option<Window> {
+    if EVENTS.load(Ordering::SeqCst) {
+        FOCUSED.lock().unwrap().clone()
+    } else {
+        crate::active_window_title_and_process()
+    }
+}
+
+// Tests stand in for an event source
+#[cfg(all(test, target_os = "linux"))]
+pub fn use_events() {
+    EVENTS.store(true, Ordering::SeqCst);
+}
+
+// Start the event source for this platform; `wake` is notified on every change
+pub fn start(wake: Arc<Notify>) {
+    let _ = WAKE.set(wake);
+    match start_source() {
+        Some(source) => {
+            EVENTS.store(true, Ordering::SeqCst);
+            log_line(&format!("Following window focus through {}", source));
+        }
+        #[cfg(windows)]
+        None => log_line("Could not hook focus events; polling the foreground window"),
+        #[cfg(unix)]
+        None => log_line("No window source in this session (X11, sway, Hyprland, GNOME or KDE); windows won't be recorded"),
+    }
+}
+
+#[cfg(windows)]
+fn start_source() -> Option<&'static str> {
+    win_event::start().then_some("SetWinEventHook")
+}
+
+#[cfg(unix)]
+fn start_source() -> Option<&'static str> {
+    if let Some(compositor) = crate::wayland::start() {
+        return Some(compositor);
+    }
+    #[cfg(target_os = "linux")]
+    if xorg::start() {
+        return Some("X11 PropertyNotify");
+    }
+    None
+}
+
+#[cfg(windows)]
+mod win_event {
+    use windows::Win32::Foundation::{HMODULE, HWND};
+    use windows::Win32::UI::Accessibility::{SetWinEventHook, HWINEVENTHOOK};
+    use windows::Win32::UI::WindowsAndMessaging::{
+        DispatchMessageW, GetForegroundWindow, GetMessageW, TranslateMessage, EVENT_OBJECT_NAMECHANGE,
+        EVENT_SYSTEM_FOREGROUND, MSG, OBJID_WINDOW, WINEVENT_OUTOFCONTEXT,
+    };
+
+    unsafe extern "system" fn on_event(
+        _hook: HWINEVENTHOOK,
+        event: u32,
+        hwnd: HWND,
+        object: i32,
+        _child: i32,
+        _thread: u32,
+        _time: u32,
+    ) {
+        // name changes fire for every control in every window; only the
+        // foreground window's title matters
+        if event == EVENT_OBJECT_NAMECHANGE && (object != OBJID_WINDOW.0 || hwnd != GetForegroundWindow()) {
+            return;
+        }
+        super::set_focused(crate::active_window_title_and_process());
+    }
+
+    // Out-of-context hooks are delivered through the message queue of the
+    // thread that set them, so that thread runs a message loop for good
+    pub fn start() -> bool {
+        let (ready, hooked) = std::sync::mpsc::channel();
+        std::thread::spawn(move || unsafe {
+            let hook = |event| SetWinEventHook(event, event, HMODULE::default(), Some(on_event), 0, 0, WINEVENT_OUTOFCONTEXT);
+            let foreground = hook(EVENT_SYSTEM_FOREGROUND);
+            let titles = hook(EVENT_OBJECT_NAMECHANGE);
+            let _ = ready.send(!foreground.is_invalid());
+            if foreground.is_invalid() {
+                return;
+            }
+            if titles.is_invalid() {
+                crate::log_line("Could not follow window title changes; only focus changes are recorded at once");
+            }
+            super::set_focused(crate::active_window_title_and_process());
+
+            let mut message = MSG::default();
+            while GetMessageW(&mut message, HWND::default(), 0, 0).0 > 0 {
+                let _ = TranslateMessage(&message);
+                DispatchMessageW(&message);
+            }
+        });
+        hooked.recv().unwrap_or(false)
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod xorg {
+    use std::ffi::CString;
+    use std::os::raw::{c_int, c_long, c_uchar, c_ulong};
+    use x11::xlib;
+
+    struct Atoms {
+        active_window: xlib::Atom,
+        net_wm_name: xlib::Atom,
+        net_wm_pid: xlib::Atom,
+        utf8_string: xlib::Atom,
+    }
+
+    // Windows vanish between an event and our query all the time; Xlib's
+    // default handler would exit the process over the resulting BadWindow
+    unsafe extern "C" fn ignore_errors(_: *mut xlib::Display, _: *mut xlib::XErrorEvent) -> c_int {
+        0
+    }
+
+    unsafe fn atom(display: *mut xlib::Display, name: &str) -> xlib::Atom {
+        let name = CString::new(name).unwrap();
+        xlib::XInternAtom(display, name.as_ptr(), xlib::False)
+    }
+
+    // Raw property bytes, None when the window doesn't have it
+    unsafe fn property(display: *mut xlib::Display, window: xlib::Window, property: xlib::Atom, kind: xlib::Atom) -> Option<Vec<u8>> {
+        let (mut actual_kind, mut format, mut items, mut remaining) = (0 as c_ulong, 0 as c_int, 0 as c_ulong, 0 as c_ulong);
+        let mut data: *mut c_uchar = std::ptr::null_mut();
+        let status = xlib::XGetWindowProperty(
+            display, window, property, 0, 4096, xlib::False, kind,
+            &mut actual_kind, &mut format, &mut items, &mut remaining, &mut data,
+        );
+        if status != xlib::Success as c_int || data.is_null() {
+            return None;
+        }
+        // 32-bit items come back as C longs
+        let size = match format {
+            8 => 1,
+            16 => 2,
+            32 => std::mem::size_of::<c_long>(),
+            _ => 0,
+        };
+        let bytes = std::slice::from_raw_parts(data, items as usize * size).to_vec();
+        xlib::XFree(data as *mut _);
+        (actual_kind != 0).then_some(bytes)
+    }
+
+    unsafe fn cardinal(display: *mut xlib::Display, window: xlib::Window, name: xlib::Atom, kind: xlib::Atom) -> Option<c_ulong> {
+        let bytes = property(display, window, name, kind)?;
+        let value = bytes.get(..std::mem::size_of::<c_long>())?;
+        Some(c_long::from_ne_bytes(value.try_into().ok()?) as c_ulong)
+    }
+
+    unsafe fn active_window(display: *mut xlib::Display, root: xlib::Window, atoms: &Atoms) -> xlib::Window {
+        cardinal(display, root, atoms.active_window, xlib::XA_WINDOW).unwrap_or(0)
+    }
+
+    unsafe fn describe(display: *mut xlib::Display, window: xlib::Window, atoms: &Atoms) -> Option<super::Window> {
+        if window == 0 {
+            return None;
+        }
+        let title = property(display, window, atoms.net_wm_name, atoms.utf8_string)
+            .or_else(|| property(display, window, xlib::XA_WM_NAME, xlib::AnyPropertyType as xlib::Atom))
+            .map(|bytes| String::from_utf8_lossy(&bytes).to_string())
+            .unwrap_or_default();
+        // WM_CLASS is "instance\0Class\0"
+        let class = property(display, window, xlib::XA_WM_CLASS, xlib::XA_STRING)
+            .and_then(|bytes| {
+                let class = bytes.split(|b| *b == 0).nth(1)?.to_vec();
+                Some(String::from_utf8_lossy(&class).to_string())
+            })
+            .filter(|class| !class.is_empty());
+        let pid = cardinal(display, window, atoms.net_wm_pid, xlib::XA_CARDINAL).and_then(|pid| u32::try_from(pid).ok());
+        let process = pid
+            .and_then(crate::process_name_from_pid)
+            .or(class)
+            .unwrap_or_else(|| if pid.is_some() { crate::process::INACCESSIBLE } else { "Unknown" }.to_string());
+        Some(super::Window { title, process, pid })
+    }
+
+    pub fn start() -> bool {
+        if std::env::var_os("DISPLAY").is_none() {
+            return false;
+        }
+        let (ready, opened) = std::sync::mpsc::channel();
+        std::thread::spawn(move || unsafe {
+            let display = xlib::XOpenDisplay(std::ptr::null());
+            let _ = ready.send(!display.is_null());
+            if !display.is_null() {
+                follow(display);
+            }
+        });
+        opened.recv().unwrap_or(false)
+    }
+
+    // The window manager sets _NET_ACTIVE_WINDOW on the root window; title
+    // changes show up as property changes on the active window itself
+    unsafe fn follow(display: *mut xlib::Display) {
+        xlib::XSetErrorHandler(Some(ignore_errors));
+        let atoms = Atoms {
+            active_window: atom(display, "_NET_ACTIVE_WINDOW"),
+            net_wm_name: atom(display, "_NET_WM_NAME"),
+            net_wm_pid: atom(display, "_NET_WM_PID"),
+            utf8_string: atom(display, "UTF8_STRING"),
+        };
+        let root = xlib::XDefaultRootWindow(display);
+        xlib::XSelectInput(display, root, xlib::PropertyChangeMask);
+
+        let mut active = active_window(display, root, &atoms);
+        if active != 0 {
+            xlib::XSelectInput(display, active, xlib::PropertyChangeMask);
+        }
+        super::set_focused(describe(display, active, &atoms));
+
+        let mut event: xlib::XEvent = std::mem::zeroed();
+        loop {
+            xlib::XNextEvent(display, &mut event);
+            if event.get_type() != xlib::PropertyNotify {
+                continue;
+            }
+            let change = event.property;
+            if change.window == root && change.atom == atoms.active_window {
+                let now = active_window(display, root, &atoms);
+                if now != active {
+                    if active != 0 {
+                        xlib::XSelectInput(display, active, xlib::NoEventMask);
+                    }
+                    if now != 0 {
+                        xlib::XSelectInput(display, now, xlib::PropertyChangeMask);
+                    }
+                    active = now;
+                }
+                super::set_focused(describe(display, active, &atoms));
+            } else if change.window == active && (change.atom == atoms.net_wm_name || change.atom == xlib::XA_WM_NAME) {
+                super::set_focused(describe(display, active, &atoms));
+            }
+        }
+    }
+}
@@ -0,0 +1,148 @@
+use regex::Regex;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+use crate::process::ProcessInfo;
+
+// -------------------- git attribution --------------------
+// Time per repository and branch rather than per editor: a path from the
+// focused window (the terminal's working directory, else a path in the
+// title) is followed up to the enclosing repository, whose HEAD names the
+// branch. .git is read directly; focus changes don't start git processes.
+
+// Absolute or home-relative paths in titles such as "main.rs (~/src/chronos) - VIM",
+// "me@host: ~/src/chronos" or "C:\src\chronos\main.rs - Notepad++"
+const TITLE_PATH_PATTERN: &str = r#"(?:^|[\s(\[:'"])((?:~|[A-Za-z]:)?[/\\][^\s()\[\]'"|<>*?]+)"#;
+
+pub fn home_dir() -> Option<PathBuf> {
+    std::env::var_os(if cfg!(windows) { "USERPROFILE" } else { "HOME" }).map(PathBuf::from)
+}
+
+fn title_paths(title: &str) -> Vec<PathBuf> {
+    static PATTERN: OnceLock<Regex> = OnceLock::new();
+    let pattern = PATTERN.get_or_init(|| Regex::new(TITLE_PATH_PATTERN).unwrap());
+    pattern
+        .captures_iter(title)
+        .filter_map(|caps| {
+            let path = caps[1].trim_end_matches(['.', ',', ';']);
+            match path.strip_prefix('~') {
+                Some(rest) => Some(home_dir()?.join(rest.trim_start_matches(['/', '\\']))),
+                None => Some(PathBuf::from(path)),
+            }
+        })
+        .collect()
+}
+
+// The git directory a .git entry points to: the directory itself, or for
+// worktrees and submodules a file saying "gitdir: <path>"
+fn git_dir(dot_git: &Path) -> Option<PathBuf> {
+    if dot_git.is_dir() {
+        return Some(dot_git.to_path_buf());
+    }
+    let content = std::fs::read_to_string(dot_git).ok()?;
+    let target = content.trim().strip_prefix("gitdir:")?.trim();
+    Some(dot_git.parent()?.join(target))
+}
+
+// "ref: refs/heads/main" names the branch; a detached HEAD holds the commit
+// itself, shortened like git does
+fn branch(git_dir: &Path) -> Option<String> {
+    let head = std::fs::read_to_string(git_dir.join("HEAD")).ok()?;
+    let head = head.trim();
+    match head.strip_prefix("ref:") {
+        Some(reference) => {
+            let reference = reference.trim();
+            Some(reference.strip_prefix("refs/heads/").unwrap_or(reference).to_string())
+        }
+        None => head.get(..7).map(str::to_string),
+    }
+}
+
+// (top-level directory, branch) of the repository containing `path`, which
+// may name a file
+pub fn repository(path: &Path) -> Option<(PathBuf, Option<String>)> {
+    for dir in path.ancestors() {
+        let dot_git = dir.join(".git");
+        if !dot_git.exists() {
+            continue;
+        }
+        return Some((dir.to_path_buf(), git_dir(&dot_git).and_then(|git_dir| branch(&git_dir))));
+    }
+    None
+}
+
+// Attach the repository of the focused window, if it is in one
+pub fn attribute(mut info: ProcessInfo, title: &str) -> ProcessInfo {
+    let candidates = info.working_directory.iter().map(PathBuf::from).chain(title_paths(title));
+    if let Some((dir, branch)) = candidates.filter_map(|path| repository(&path)).next() {
+        info.repository = dir.file_name().map(|name| name.to_string_lossy().to_string());
+        info.repository_path = Some(dir.to_string_lossy().to_string());
+        info.branch = branch;
+    }
+    info
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write(path: &Path, content: &str) {
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, content).unwrap();
+    }
+
+    #[test]
+    fn repositories_and_branches() {
+        let root = std::env::temp_dir().join(format!("chronos-git-test-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+
+        let repo = root.join("chronos");
+        write(&repo.join(".git/HEAD"), "ref: refs/heads/main\n");
+        write(&repo.join("src/main.rs"), "");
+        // a path naming a file, or nothing that exists yet
+        assert_eq!(repository(&repo.join("src/main.rs")), Some((repo.clone(), Some("main".to_string()))));
+        assert_eq!(repository(&repo.join("target/new")), Some((repo.clone(), Some("main".to_string()))));
+
+        let detached = root.join("detached");
+        write(&detached.join(".git/HEAD"), "0123456789abcdef0123456789abcdef01234567\n");
+        assert_eq!(repository(&detached), Some((detached.clone(), Some("0123456".to_string()))));
+
+        // worktrees and submodules have a .git file pointing at the git directory
+        let worktree = root.join("chronos-feature");
+        write(&worktree.join(".git"), "gitdir: ../chronos/.git/worktrees/chronos-feature\n");
+        write(&repo.join(".git/worktrees/chronos-feature/HEAD"), "ref: refs/heads/feature/sync\n");
+        assert_eq!(repository(&worktree.join("README.md")), Some((worktree.clone(), Some("feature/sync".to_string()))));
+        let submodule = repo.join("vendor/lib");
+        write(&submodule.join(".git"), &format!("gitdir: {}\n", repo.join(".git/modules/lib").display()));
+        write(&repo.join(".git/modules/lib/HEAD"), "ref: refs/heads/stable\n");
+        assert_eq!(repository(&submodule), Some((submodule.clone(), Some("stable".to_string()))));
+        // a .git file pointing nowhere still marks the repository
+        let broken = root.join("broken");
+        write(&broken.join(".git"), "gitdir: /nonexistent\n");
+        assert_eq!(repository(&broken), Some((broken.clone(), None)));
+
+        assert_eq!(repository(&root), None);
+
+        let info = attribute(ProcessInfo { working_directory: Some(repo.join("src").display().to_string()), ..ProcessInfo::default() }, "");
+        assert_eq!(
+            (info.repository.as_deref(), info.repository_path, info.branch.as_deref()),
+            (Some("chronos"), Some(repo.display().to_string()), Some("main"))
+        );
+        // from a path in the title when there is no working directory
+        let title = format!("main.rs ({}) - NVIM", worktree.display());
+        let info = attribute(ProcessInfo::default(), &title);
+        assert_eq!((info.repository.as_deref(), info.branch.as_deref()), (Some("chronos-feature"), Some("feature/sync")));
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn paths_in_titles() {
+        let home = home_dir().unwrap();
+        assert_eq!(title_paths("main.rs (~/src/chronos) - VIM"), vec![home.join("src/chronos")]);
+        assert_eq!(title_paths("me@host: ~/src/chronos"), vec![home.join("src/chronos")]);
+        assert_eq!(title_paths(r"C:\src\chronos\main.rs - Notepad++"), vec![PathBuf::from(r"C:\src\chronos\main.rs")]);
+        assert_eq!(title_paths("'/etc/hosts' [readonly]."), vec![PathBuf::from("/etc/hosts")]);
+        assert_eq!(title_paths("Inbox - Gmail - 2/3 unread"), Vec::<PathBuf>::new());
+    }
+}
@@ -0,0 +1,175 @@
+use serde::{Deserialize, Serialize};
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Seek, Write};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::{get_app_data_dir, log_line};
+
+// -------------------- single instance --------------------
+// Only one tracker may run per user: two loops would write the same
+// activity log and sync every entry twice. The tracker holds an exclusive
+// advisory lock (flock, LockFileEx) on chronos.lock in the data directory
+// and writes its pid into it. The system releases the lock when the process
+// exits, however it exits, so there is no stale lock to detect or take over;
+// the file itself stays.
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct LockInfo {
+    pub pid: u32,
+    // Local "%Y-%m-%d %H:%M:%S" timestamp
+    pub started_at: String,
+}
+
+// `chronos status` takes a shared lock for a moment to see whether the file
+// is held, so a tracker starting right then tries again before giving up
+const LOCK_ATTEMPTS: u32 = 20;
+const LOCK_RETRY: Duration = Duration::from_millis(50);
+
+// Holds the lock until dropped
+pub struct InstanceLock {
+    _file: Option<File>,
+}
+
+fn lock_path() -> PathBuf {
+    get_app_data_dir().join("chronos.lock")
+}
+
+// Ok(false) when another process holds a conflicting lock
+#[cfg(unix)]
+fn try_lock(file: &File, exclusive: bool) -> std::io::Result<bool> {
+    use std::os::unix::io::AsRawFd;
+
+    let operation = if exclusive { libc::LOCK_EX } else { libc::LOCK_SH };
+    if unsafe { libc::flock(file.as_raw_fd(), operation | libc::LOCK_NB) } == 0 {
+        return Ok(true);
+    }
+    let e = std::io::Error::last_os_error();
+    match e.kind() {
+        std::io::ErrorKind::WouldBlock => Ok(false),
+        _ => Err(e),
+    }
+}
+
+// Locks one byte far past the end: Windows locks are mandatory, and the
+// pid at the start has to stay readable
+#[cfg(windows)]
+fn try_lock(file: &File, exclusive: bool) -> std::io::Result<bool> {
+    use std::os::windows::io::AsRawHandle;
+    use windows::Win32::Foundation::{ERROR_LOCK_VIOLATION, HANDLE};
+    use windows::Win32::Storage::FileSystem::{LockFileEx, LOCKFILE_EXCLUSIVE_LOCK, LOCKFILE_FAIL_IMMEDIATELY};
+    use windows::Win32::System::IO::OVERLAPPED;
+
+    let mut flags = LOCKFILE_FAIL_IMMEDIATELY;
+    if exclusive {
+        flags |= LOCKFILE_EXCLUSIVE_LOCK;
+    }
+    let mut overlapped = OVERLAPPED::default();
+    overlapped.Anonymous.Anonymous.OffsetHigh = 0x7fff_ffff;
+    match unsafe { LockFileEx(HANDLE(file.as_raw_handle()), flags, 0, 1, 0, &mut overlapped) } {
+        Ok(()) => Ok(true),
+        Err(e) if e.code() == ERROR_LOCK_VIOLATION.to_hresult() => Ok(false),
+        Err(e) => Err(std::io::Error::from_raw_os_error(e.code().0 & 0xffff)),
+    }
+}
+
+fn read_info(file: &mut File) -> Option<LockInfo> {
+    let mut content = String::new();
+    file.read_to_string(&mut content).ok()?;
+    serde_json::from_str(&content).ok()
+}
+
+// The tracker holding the lock, if one is running
+pub fn running_instance() -> Option<LockInfo> {
+    running_instance_at(&lock_path())
+}
+
+fn running_instance_at(path: &Path) -> Option<LockInfo> {
+    let mut file = File::open(path).ok()?;
+    // a shared lock is only refused while a tracker holds its exclusive one
+    // (and is given up again when `file` is closed)
+    if try_lock(&file, false).unwrap_or(true) {
+        return None;
+    }
+    // a tracker that is starting may not have written its pid yet
+    Some(read_info(&mut file).unwrap_or(LockInfo { pid: 0, started_at: String::new() }))
+        .filter(|info| info.pid != std::process::id())
+}
+
+impl InstanceLock {
+    // Err(info) when another tracker already holds the lock
+    pub fn acquire() -> Result<InstanceLock, LockInfo> {
+        InstanceLock::acquire_at(&lock_path())
+    }
+
+    fn acquire_at(path: &Path) -> Result<InstanceLock, LockInfo> {
+        let mut file = match OpenOptions::new().read(true).write(true).create(true).truncate(false).open(path) {
+            Ok(file) => file,
+            Err(e) => {
+                // e.g. a read-only data directory; run unguarded rather than not at all
+                log_line(&format!("Could not open {}: {}", path.display(), e));
+                return Ok(InstanceLock { _file: None });
+            }
+        };
+        let mut attempts = 1;
+        loop {
+            match try_lock(&file, true) {
+                Ok(true) => break,
+                Ok(false) if attempts < LOCK_ATTEMPTS => {
+                    attempts += 1;
+                    std::thread::sleep(LOCK_RETRY);
+                }
+                Ok(false) => return Err(read_info(&mut file).unwrap_or(LockInfo { pid: 0, started_at: String::new() })),
+                Err(e) => {
+                    log_line(&format!("Could not lock {}: {}", path.display(), e));
+                    return Ok(InstanceLock { _file: None });
+                }
+            }
+        }
+
+        let info = LockInfo { pid: std::process::id(), started_at: crate::sync_status::now() };
+        let written = file
+            .set_len(0)
+            .and_then(|_| file.rewind())
+            .and_then(|_| file.write_all(serde_json::to_string(&info).unwrap_or_default().as_bytes()));
+        if let Err(e) = written {
+            log_line(&format!("Could not write {}: {}", path.display(), e));
+        }
+        Ok(InstanceLock { _file: Some(file) })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn status_probe_during_acquire() {
+        let path = std::env::temp_dir().join(format!("chronos-lock-test-{}", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+
+        // `chronos status` has the file open with its shared lock while a
+        // tracker starts
+        let probe = File::create(&path).unwrap();
+        assert!(try_lock(&probe, false).unwrap());
+        let probe_done = std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(200));
+            drop(probe);
+        });
+        let lock = InstanceLock::acquire_at(&path).expect("the probe kept the tracker from starting");
+        probe_done.join().unwrap();
+
+        // the tracker's lock is what the next probe sees, and what keeps a
+        // second tracker out
+        let probe = File::open(&path).unwrap();
+        assert!(!try_lock(&probe, false).unwrap());
+        drop(probe);
+        let info = InstanceLock::acquire_at(&path).err().expect("a second tracker got the lock");
+        assert_eq!(info.pid, std::process::id());
+
+        drop(lock);
+        assert!(running_instance_at(&path).is_none());
+        let _ = std::fs::remove_file(&path);
+    }
+}
@@ -0,0 +1,381 @@
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::error::Error;
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader};
+use tokio::sync::oneshot;
+
+use crate::log_line;
+use crate::pause::{self, PauseMode, PauseState};
+use crate::sync_status::SyncStatus;
+use crate::tracker::Tracker;
+
+// -------------------- local control API --------------------
+// The running tracker answers JSON-RPC 2.0 requests on a Unix socket (a
+// named pipe on Windows), one JSON object per line:
+//
+//   -> {"jsonrpc":"2.0","id":1,"method":"pause","params":{"until":"30m"}}
+//   <- {"jsonrpc":"2.0","id":1,"result":{"paused":true,"pausedUntil":"..."}}
+//
+// Methods: status, pause {until?, mode?}, resume, sync, session,
+// events {limit?}. `until` takes what `chronos pause` does ("30m",
+// "until tomorrow"); `mode` is "paused" (default) or "private".
+// Only the current user can send requests: the socket is 0600, and the pipe
+// keeps the default security descriptor, which gives write access to its
+// owner (and administrators) only.
+
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+const SERVER_ERROR: i64 = -32000;
+
+const DEFAULT_EVENT_LIMIT: usize = 20;
+
+#[cfg(unix)]
+pub fn socket_path() -> std::path::PathBuf {
+    std::env::var_os("XDG_RUNTIME_DIR")
+        .map(std::path::PathBuf::from)
+        .filter(|dir| dir.is_dir())
+        .unwrap_or_else(crate::get_app_data_dir)
+        .join("chronos.sock")
+}
+
+#[cfg(windows)]
+pub fn pipe_name() -> String {
+    format!(r"\\.\pipe\chronos-{}", whoami::username())
+}
+
+#[derive(Deserialize)]
+struct Request {
+    #[serde(default)]
+    id: Value,
+    method: String,
+    #[serde(default)]
+    params: Value,
+}
+
+struct RpcError {
+    code: i64,
+    message: String,
+}
+
+impl RpcError {
+    fn new(code: i64, message: impl Into<String>) -> RpcError {
+        RpcError { code, message: message.into() }
+    }
+}
+
+fn error_response(id: Value, code: i64, message: &str) -> Value {
+    json!({ "jsonrpc": "2.0", "id": id, "error": { "code": code, "message": message } })
+}
+
+fn status(tracker: &Tracker) -> Value {
+    let pause = PauseState::load();
+    let paused = pause.is_paused();
+    let sync = SyncStatus::load();
+    let state = tracker.state.lock().unwrap();
+    json!({
+        "pid": std::process::id(),
+        "startedAt": state.started_at,
+        "paused": paused,
+        "mode": if paused { Some(pause.mode) } else { None },
+        "pausedSince": if paused { pause.paused_since } else { None },
+        "pausedUntil": if paused { pause.paused_until } else { None },
+        "session": state.session,
+        "sync": sync,
+    })
+}
+
+async fn dispatch(method: &str, params: &Value, tracker: &Tracker) -> Result<Value, RpcError> {
+    match method {
+        "status" => Ok(status(tracker)),
+        "pause" => {
+            let until = match params.get("until") {
+                None | Some(Value::Null) => None,
+                Some(Value::String(text)) => Some(
+                    pause::parse_until(text, chrono::Local::now().naive_local())
+                        .ok_or_else(|| RpcError::new(INVALID_PARAMS, format!("invalid pause end '{}'", text)))?,
+                ),
+                Some(_) => return Err(RpcError::new(INVALID_PARAMS, "until must be a string like \"30m\"")),
+            };
+            let mode = match params.get("mode") {
+                None | Some(Value::Null) => PauseMode::Paused,
+                Some(mode) => serde_json::from_value(mode.clone())
+                    .map_err(|_| RpcError::new(INVALID_PARAMS, "mode must be \"paused\" or \"private\""))?,
+            };
+            let state = PauseState::pause(mode, until).map_err(|e| RpcError::new(SERVER_ERROR, e.to_string()))?;
+            tracker.wake.notify_one();
+            Ok(json!({
+                "paused": true,
+                "mode": state.mode,
+                "pausedSince": state.paused_since,
+                "pausedUntil": state.paused_until,
+            }))
+        }
+        "resume" => {
+            let was_paused = PauseState::resume().map_err(|e| RpcError::new(SERVER_ERROR, e.to_string()))?;
+            tracker.wake.notify_one();
+            Ok(json!({ "paused": false, "wasPaused": was_paused }))
+        }
+        "sync" => {
+            let (reply, answer) = oneshot::channel();
+            tracker
+                .sync_now
+                .send(reply)
+                .await
+                .map_err(|_| RpcError::new(SERVER_ERROR, "the sync task is not running"))?;
+            match answer.await {
+                Ok(Ok(())) => Ok(json!({ "synced": true, "lastSync": SyncStatus::load().last_sync })),
+                Ok(Err(e)) => Err(RpcError::new(SERVER_ERROR, e)),
+                Err(_) => Err(RpcError::new(SERVER_ERROR, "the sync task stopped")),
+            }
+        }
+        "session" => Ok(json!(tracker.state.lock().unwrap().session)),
+        "events" => {
+            let limit = match params.get("limit") {
+                None | Some(Value::Null) => DEFAULT_EVENT_LIMIT,
+                Some(limit) => limit
+                    .as_u64()
+                    .ok_or_else(|| RpcError::new(INVALID_PARAMS, "limit must be a positive number"))?
+                    as usize,
+            };
+            let state = tracker.state.lock().unwrap();
+            let skip = state.recent.len().saturating_sub(limit);
+            Ok(json!(state.recent.iter().skip(skip).collect::<Vec<_>>()))
+        }
+        _ => Err(RpcError::new(METHOD_NOT_FOUND, format!("unknown method '{}'", method))),
+    }
+}
+
+async fn handle_line(line: &str, tracker: &Tracker) -> Value {
+    let value: Value = match serde_json::from_str(line) {
+        Ok(value) => value,
+        Err(e) => return error_response(Value::Null, PARSE_ERROR, &e.to_string()),
+    };
+    let request: Request = match serde_json::from_value(value) {
+        Ok(request) => request,
+        Err(e) => return error_response(Value::Null, INVALID_REQUEST, &e.to_string()),
+    };
+    match dispatch(&request.method, &request.params, tracker).await {
+        Ok(result) => json!({ "jsonrpc": "2.0", "id": request.id, "result": result }),
+        Err(e) => error_response(request.id, e.code, &e.message),
+    }
+}
+
+async fn handle_connection<S>(stream: S, tracker: Tracker)
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let mut stream = BufReader::new(stream);
+    let mut line = String::new();
+    loop {
+        line.clear();
+        match stream.read_line(&mut line).await {
+            Ok(0) | Err(_) => return,
+            Ok(_) => {}
+        }
+        if line.trim().is_empty() {
+            continue;
+        }
+        let mut response = handle_line(&line, &tracker).await.to_string();
+        response.push('\n');
+        if stream.write_all(response.as_bytes()).await.is_err() {
+            return;
+        }
+    }
+}
+
+#[cfg(unix)]
+pub async fn serve(tracker: Tracker) {
+    serve_at(socket_path(), tracker).await
+}
+
+#[cfg(unix)]
+async fn serve_at(path: std::path::PathBuf, tracker: Tracker) {
+    use std::os::unix::fs::PermissionsExt;
+    use tokio::net::{UnixListener, UnixStream};
+
+    if UnixStream::connect(&path).await.is_ok() {
+        log_line(&format!("Another tracker is listening on {}, control API disabled", path.display()));
+        return;
+    }
+    // Left behind by a tracker that didn't shut down cleanly
+    let _ = std::fs::remove_file(&path);
+
+    let listener = match UnixListener::bind(&path) {
+        Ok(listener) => listener,
+        Err(e) => {
+            log_line(&format!("Control API unavailable ({}): {}", path.display(), e));
+            return;
+        }
+    };
+    if let Err(e) = std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o600)) {
+        log_line(&format!("Could not restrict {}: {}", path.display(), e));
+    }
+    log_line(&format!("Control API listening on {}", path.display()));
+
+    loop {
+        match listener.accept().await {
+            Ok((stream, _)) => {
+                tokio::spawn(handle_connection(stream, tracker.clone()));
+            }
+            Err(e) => {
+                log_line(&format!("Control API accept failed: {}", e));
+                tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+            }
+        }
+    }
+}
+
+#[cfg(windows)]
+pub async fn serve(tracker: Tracker) {
+    use tokio::net::windows::named_pipe::ServerOptions;
+
+    let name = pipe_name();
+    let mut server = match ServerOptions::new().first_pipe_instance(true).create(&name) {
+        Ok(server) => server,
+        Err(e) => {
+            log_line(&format!("Control API unavailable ({}): {}", name, e));
+            return;
+        }
+    };
+    log_line(&format!("Control API listening on {}", name));
+
+    loop {
+        let connected = server.connect().await;
+        // Always have an instance waiting for the next client
+        let next = match ServerOptions::new().create(&name) {
+            Ok(next) => next,
+            Err(e) => {
+                log_line(&format!("Control API stopped: {}", e));
+                return;
+            }
+        };
+        let client = std::mem::replace(&mut server, next);
+        match connected {
+            Ok(()) => {
+                tokio::spawn(handle_connection(client, tracker.clone()));
+            }
+            Err(e) => log_line(&format!("Control API connect failed: {}", e)),
+        }
+    }
+}
+
+#[cfg(unix)]
+type ClientStream = tokio::net::UnixStream;
+#[cfg(windows)]
+type ClientStream = tokio::net::windows::named_pipe::NamedPipeClient;
+
+pub struct Client {
+    stream: BufReader<ClientStream>,
+    next_id: u64,
+}
+
+impl Client {
+    // None when no tracker is running
+    pub async fn connect() -> Option<Client> {
+        #[cfg(unix)]
+        let stream = tokio::net::UnixStream::connect(socket_path()).await.ok()?;
+
+        #[cfg(windows)]
+        let stream = {
+            use tokio::net::windows::named_pipe::ClientOptions;
+            use windows::Win32::Foundation::ERROR_PIPE_BUSY;
+
+            let name = pipe_name();
+            let mut attempts = 0;
+            loop {
+                match ClientOptions::new().open(&name) {
+                    Ok(client) => break client,
+                    // Every instance is serving someone; a new one follows right away
+                    Err(e) if e.raw_os_error() == Some(ERROR_PIPE_BUSY.0 as i32) && attempts < 20 => {
+                        attempts += 1;
+                        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+                    }
+                    Err(_) => return None,
+                }
+            }
+        };
+
+        Some(Client { stream: BufReader::new(stream), next_id: 1 })
+    }
+
+    pub async fn call(&mut self, method: &str, params: Value) -> Result<Value, Box<dyn Error>> {
+        let id = self.next_id;
+        self.next_id += 1;
+
+        let mut request = json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params }).to_string();
+        request.push('\n');
+        self.stream.write_all(request.as_bytes()).await?;
+
+        let mut line = String::new();
+        if self.stream.read_line(&mut line).await? == 0 {
+            return Err("the tracker closed the connection".into());
+        }
+        let mut response: Value = serde_json::from_str(&line)?;
+        if let Some(error) = response.get("error") {
+            let message = error.get("message").and_then(|m| m.as_str()).unwrap_or("unknown error");
+            return Err(message.to_string().into());
+        }
+        Ok(response.get_mut("result").map(Value::take).unwrap_or(Value::Null))
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn round_trip() {
+        // pause.json and sync_status.json go to the data directory
+        let dir = std::env::temp_dir().join(format!("chronos-ipc-test-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        crate::use_test_data_dir(Some(dir.clone()));
+
+        let (tracker, mut sync_requests) = Tracker::new("2026-03-02 09:00:00".to_string());
+        // the sync task
+        tokio::spawn(async move {
+            while let Some(reply) = sync_requests.recv().await {
+                let _ = reply.send(Ok(()));
+            }
+        });
+        let path = dir.join("chronos.sock");
+        tokio::spawn(serve_at(path.clone(), tracker.clone()));
+        let mut client = None;
+        for _ in 0..50 {
+            if let Ok(stream) = tokio::net::UnixStream::connect(&path).await {
+                client = Some(Client { stream: BufReader::new(stream), next_id: 1 });
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        let mut client = client.expect("the control API did not start");
+
+        let status = client.call("status", Value::Null).await.unwrap();
+        assert_eq!(status["pid"], std::process::id());
+        assert_eq!(status["startedAt"], "2026-03-02 09:00:00");
+        assert_eq!(status["paused"], false);
+
+        let paused = client.call("pause", json!({ "until": "30m", "mode": "private" })).await.unwrap();
+        assert_eq!((&paused["paused"], &paused["mode"]), (&json!(true), &json!("private")));
+        assert!(paused["pausedUntil"].is_string());
+        let status = client.call("status", Value::Null).await.unwrap();
+        assert_eq!((&status["paused"], &status["mode"]), (&json!(true), &json!("private")));
+
+        let error = client.call("pause", json!({ "until": 30 })).await.unwrap_err();
+        assert_eq!(error.to_string(), "until must be a string like \"30m\"");
+
+        let resumed = client.call("resume", Value::Null).await.unwrap();
+        assert_eq!(resumed, json!({ "paused": false, "wasPaused": true }));
+
+        let synced = client.call("sync", Value::Null).await.unwrap();
+        assert_eq!(synced["synced"], true);
+
+        let error = client.call("reboot", Value::Null).await.unwrap_err();
+        assert_eq!(error.to_string(), "unknown method 'reboot'");
+
+        crate::use_test_data_dir(None);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,267 @@
+use regex::{Captures, Regex};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::config::Config;
+use crate::log_line;
+use crate::LogEntry;
+
+// -------------------- issue references --------------------
+// Timesheets are per ticket, and ticket references already show up in window
+// titles ("PROJ-123 Fix login - Jira"), URLs (github.com/org/repo/issues/45,
+// linear.app/acme/issue/ENG-7) and branch names (feature/proj-123-login).
+// Entries read back from the log are tagged with the issues they mention, so
+// pattern changes apply to old entries too; the running tracker tags the
+// current session the same way.
+//
+// Built in: Jira/Linear style keys ("PROJ-123") and GitHub references,
+// written "org/repo#45". Patterns from the "issues" setting come on top.
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default, rename_all = "camelCase")]
+pub struct IssueConfig {
+    pub enabled: bool,
+    // Only count keys of these projects, e.g. ["PROJ", "ENG"]; any key when
+    // empty. Cuts out look-alikes such as "ABC-1" in unrelated titles, and
+    // lets lower-case keys in branch names count ("feature/proj-123").
+    pub key_prefixes: Vec<String>,
+    pub patterns: Vec<IssuePattern>,
+}
+
+impl Default for IssueConfig {
+    fn default() -> Self {
+        IssueConfig { enabled: true, key_prefixes: Vec::new(), patterns: Vec::new() }
+    }
+}
+
+// e.g. { "pattern": "\\bTicket (\\d+)", "issue": "TICKET-$1" }; the whole
+// match is the issue when `issue` is unset
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(default, rename_all = "camelCase")]
+pub struct IssuePattern {
+    pub pattern: String,
+    pub issue: Option<String>,
+}
+
+// Upper-case words followed by a number that aren't issue keys
+const NOT_KEYS: &[&str] = &[
+    "AES", "COVID", "CVE", "DDR", "ECMA", "ES", "GPT", "HTTP", "ISO", "MD", "PEP", "RFC", "RSA", "SHA", "SSL", "TLS", "USB", "UTF", "WIN",
+];
+
+const KEY_PATTERN: &str = r"\b([A-Z][A-Z0-9]{1,9})-([1-9][0-9]{0,6})\b";
+// The key starts a path segment of the branch. Lower-case ones only count
+// for the configured prefixes: "fix-123" and "renovate/serde-1" are no keys.
+const BRANCH_KEY_PATTERN: &str = r"(?i)(?:^|/)([a-z][a-z0-9]{1,9})-([1-9][0-9]{0,6})(?:$|[-_/])";
+const GITHUB_URL_PATTERN: &str = r"github\.com/([\w.-]+/[\w.-]+)/(?:issues|pull)/([0-9]+)";
+// "Fix login · Issue #45 · org/repo · GitHub"
+const GITHUB_TITLE_PATTERN: &str = r"(?:Issue|Pull Request) #([0-9]+) · ([\w.-]+/[\w.-]+)";
+// "org/repo#45". GitHub owners have no dots, and the reference doesn't go on
+// from a path, so "example.com/page#3" and "/docs/intro#2" are no issues.
+const GITHUB_REFERENCE_PATTERN: &str = r"(?:^|[^\w./#-])([A-Za-z0-9][A-Za-z0-9-]{0,38}/[\w.-]+)#([0-9]+)\b";
+
+pub struct IssueDetector {
+    enabled: bool,
+    key_prefixes: Vec<String>,
+    key: Regex,
+    branch_key: Regex,
+    github_url: Regex,
+    github_title: Regex,
+    github_reference: Regex,
+    patterns: Vec<(Regex, Option<String>)>,
+}
+
+fn push(issues: &mut Vec<String>, issue: String) {
+    if !issue.is_empty() && !issues.contains(&issue) {
+        issues.push(issue);
+    }
+}
+
+impl IssueDetector {
+    pub fn from_config(config: &Config) -> IssueDetector {
+        let patterns = config
+            .issues
+            .patterns
+            .iter()
+            .filter_map(|rule| match Regex::new(&rule.pattern) {
+                Ok(re) => Some((re, rule.issue.clone())),
+                Err(e) => {
+                    log_line(&format!("Ignoring issue pattern '{}': {}", rule.pattern, e));
+                    None
+                }
+            })
+            .collect();
+        IssueDetector {
+            enabled: config.issues.enabled,
+            key_prefixes: config.issues.key_prefixes.iter().map(|prefix| prefix.trim().to_uppercase()).collect(),
+            key: Regex::new(KEY_PATTERN).unwrap(),
+            branch_key: Regex::new(BRANCH_KEY_PATTERN).unwrap(),
+            github_url: Regex::new(GITHUB_URL_PATTERN).unwrap(),
+            github_title: Regex::new(GITHUB_TITLE_PATTERN).unwrap(),
+            github_reference: Regex::new(GITHUB_REFERENCE_PATTERN).unwrap(),
+            patterns,
+        }
+    }
+
+    fn keys(&self, re: &Regex, text: &str, issues: &mut Vec<String>) {
+        for caps in re.captures_iter(text) {
+            let project = caps[1].to_uppercase();
+            let wanted = if self.key_prefixes.is_empty() {
+                caps[1] == project && !NOT_KEYS.contains(&project.as_str())
+            } else {
+                self.key_prefixes.contains(&project)
+            };
+            if wanted {
+                push(issues, format!("{}-{}", project, &caps[2]));
+            }
+        }
+    }
+
+    fn github(re: &Regex, text: &str, issues: &mut Vec<String>, issue: impl Fn(&Captures) -> String) {
+        for caps in re.captures_iter(text) {
+            push(issues, issue(&caps));
+        }
+    }
+
+    // Issues mentioned by a title, URL and branch, in order of appearance
+    pub fn find(&self, title: &str, url: Option<&str>, branch: Option<&str>) -> Vec<String> {
+        let mut issues = Vec::new();
+        if !self.enabled {
+            return issues;
+        }
+        let texts: Vec<&str> = [Some(title), url, branch].into_iter().flatten().collect();
+        for text in &texts {
+            for (re, issue) in &self.patterns {
+                for caps in re.captures_iter(text) {
+                    let mut expanded = String::new();
+                    match issue {
+                        Some(issue) => caps.expand(issue, &mut expanded),
+                        None => expanded.push_str(&caps[0]),
+                    }
+                    push(&mut issues, expanded);
+                }
+            }
+        }
+        self.keys(&self.key, title, &mut issues);
+        Self::github(&self.github_title, title, &mut issues, |caps| format!("{}#{}", &caps[2], &caps[1]));
+        Self::github(&self.github_reference, title, &mut issues, |caps| format!("{}#{}", &caps[1], &caps[2]));
+        if let Some(url) = url {
+            self.keys(&self.key, url, &mut issues);
+            Self::github(&self.github_url, url, &mut issues, |caps| format!("{}#{}", &caps[1], &caps[2]));
+        }
+        if let Some(branch) = branch {
+            self.keys(&self.branch_key, branch, &mut issues);
+        }
+        issues
+    }
+
+    // Set "issues" on the window and browser entries that mention any
+    pub fn tag(&self, entries: &mut [LogEntry]) {
+        for entry in entries.iter_mut() {
+            let field = |key: &str| entry.data.get(key).and_then(|v| v.as_str());
+            let issues = match entry.log_type.as_str() {
+                "window" => self.find(field("windowTitle").unwrap_or(""), None, field("branch")),
+                "browser" => self.find(field("browserTitle").unwrap_or(""), field("url"), None),
+                _ => continue,
+            };
+            if let (false, Value::Object(data)) = (issues.is_empty(), &mut entry.data) {
+                data.insert("issues".to_string(), Value::from(issues));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn detector(issues: IssueConfig) -> IssueDetector {
+        IssueDetector::from_config(&Config { issues, ..Default::default() })
+    }
+
+    fn prefixes(prefixes: &[&str]) -> IssueConfig {
+        IssueConfig { key_prefixes: prefixes.iter().map(|prefix| prefix.to_string()).collect(), ..Default::default() }
+    }
+
+    #[test]
+    fn keys_in_titles_and_urls() {
+        let detector = detector(IssueConfig::default());
+        assert_eq!(detector.find("PROJ-123 Fix login - Jira", None, None), ["PROJ-123"]);
+        assert_eq!(detector.find("ENG-7 and PROJ-123, again PROJ-123", None, None), ["ENG-7", "PROJ-123"]);
+        assert!(detector.find("Upgrade to HTTP-2, UTF-8 and SHA-256 (RFC-9110)", None, None).is_empty());
+        assert!(detector.find("proj-123 in lower case", None, None).is_empty());
+        assert_eq!(detector.find("Fix sync", Some("https://linear.app/acme/issue/ENG-7/fix-sync"), None), ["ENG-7"]);
+        assert_eq!(detector.find("[PROJ-9] Export", Some("https://acme.atlassian.net/browse/PROJ-9"), None), ["PROJ-9"]);
+    }
+
+    #[test]
+    fn key_prefixes() {
+        let detector = detector(prefixes(&["proj", " ENG"]));
+        assert_eq!(detector.find("ABC-1 blocks PROJ-2 and ENG-3", None, None), ["PROJ-2", "ENG-3"]);
+    }
+
+    #[test]
+    fn github() {
+        let detector = detector(IssueConfig::default());
+        assert_eq!(detector.find("Fix login · Issue #45 · acme/chronos · GitHub", None, None), ["acme/chronos#45"]);
+        assert_eq!(
+            detector.find("Add sync by jane · Pull Request #7 · acme/chronos", Some("https://github.com/acme/chronos/pull/7/files"), None),
+            ["acme/chronos#7"]
+        );
+        assert_eq!(detector.find("Notifications", Some("https://github.com/acme/chronos/issues/45#issuecomment-1"), None), ["acme/chronos#45"]);
+        assert_eq!(detector.find("Review acme/chronos#12 - Slack", None, None), ["acme/chronos#12"]);
+        assert_eq!(detector.find("acme/chronos#12 and (acme/sync.rs#3)", None, None), ["acme/chronos#12", "acme/sync.rs#3"]);
+        // URLs and paths with a fragment
+        for title in ["example.com/page#3 - Firefox", "Read https://site.org/docs/intro#2", "docs.rs/serde/latest#1", "~/notes/todo#4"] {
+            assert_eq!(detector.find(title, None, None), Vec::<String>::new(), "{}", title);
+        }
+    }
+
+    #[test]
+    fn branches() {
+        let any = detector(IssueConfig::default());
+        assert_eq!(any.find("", None, Some("feature/PROJ-123-login")), ["PROJ-123"]);
+        assert_eq!(any.find("", None, Some("PROJ-7")), ["PROJ-7"]);
+        for branch in ["fix-123", "renovate/serde-1", "feature/proj-123-login", "release-2024", "v1-2"] {
+            assert!(any.find("", None, Some(branch)).is_empty(), "{}", branch);
+        }
+
+        let proj = detector(prefixes(&["PROJ"]));
+        assert_eq!(proj.find("", None, Some("feature/proj-123-login")), ["PROJ-123"]);
+        assert_eq!(proj.find("", None, Some("proj-45_fix")), ["PROJ-45"]);
+        assert!(proj.find("", None, Some("fix-123")).is_empty());
+        // not in the middle of a segment
+        assert!(proj.find("", None, Some("feature/oldproj-123")).is_empty());
+    }
+
+    #[test]
+    fn user_patterns() {
+        let pattern = |pattern: &str, issue: Option<&str>| IssuePattern { pattern: pattern.to_string(), issue: issue.map(str::to_string) };
+        let detector = detector(IssueConfig {
+            patterns: vec![pattern(r"\bTicket (\d+)", Some("TICKET-$1")), pattern(r"\bINC\d{7}\b", None), pattern("(unclosed", None)],
+            ..Default::default()
+        });
+        assert_eq!(detector.find("Ticket 42 about PROJ-1 - Helpdesk", None, None), ["TICKET-42", "PROJ-1"]);
+        assert_eq!(detector.find("INC0012345 - ServiceNow", None, None), ["INC0012345"]);
+    }
+
+    #[test]
+    fn disabled() {
+        let detector = detector(IssueConfig { enabled: false, ..Default::default() });
+        assert!(detector.find("PROJ-123 Fix login", None, Some("PROJ-123")).is_empty());
+    }
+
+    #[test]
+    fn tag() {
+        let entry = |log_type: &str, data: Value| LogEntry { timestamp: "2026-03-02 09:00:00".to_string(), log_type: log_type.to_string(), data };
+        let mut entries = vec![
+            entry("window", json!({ "windowTitle": "main.rs - chronos", "branch": "feature/PROJ-5-sync" })),
+            entry("browser", json!({ "browserTitle": "Fix login", "url": "https://github.com/acme/chronos/issues/45" })),
+            entry("window", json!({ "windowTitle": "Inbox" })),
+            entry("tracker", json!({ "event": "PROJ-1" })),
+        ];
+        detector(IssueConfig::default()).tag(&mut entries);
+        let issues: Vec<&Value> = entries.iter().map(|e| &e.data["issues"]).collect();
+        assert_eq!(issues, [&json!(["PROJ-5"]), &json!(["acme/chronos#45"]), &Value::Null, &Value::Null]);
+    }
+}
@@ -1,54 +1,141 @@
 use chrono::Local;
+use clap::Parser;
 use rusqlite::{Connection, Result as SqlResult};
 use rdev::{listen, Event};
 use serde::{Deserialize, Serialize};
 use std::fs::{self, OpenOptions};
-use std::io::{self, Write};
+use std::collections::HashMap;
+use std::io::Write;
 use std::path::{Path, PathBuf};
 use std::sync::atomic::{AtomicI64, Ordering};
 use std::sync::Arc;
 use std::thread;
-use std::time::Duration;
-use std::process::Command;
-use windows::Win32::Foundation::{CloseHandle, HANDLE, HWND};
+use std::time::{Duration, Instant};
+#[cfg(windows)]
+use windows::Win32::Foundation::HWND;
+#[cfg(windows)]
 use windows::Win32::System::Console::FreeConsole;
-use windows::Win32::System::ProcessStatus::GetModuleBaseNameW;
-use windows::Win32::System::Threading::{OpenProcess, PROCESS_QUERY_INFORMATION, PROCESS_VM_READ};
+#[cfg(windows)]
 use windows::Win32::UI::WindowsAndMessaging::{
-    GetForegroundWindow, GetWindowTextW, GetWindowThreadProcessId,
+    GetClassNameW, GetForegroundWindow, GetWindowTextW, GetWindowThreadProcessId,
 };
+#[cfg(windows)]
 use windows::Win32::UI::Input::KeyboardAndMouse::GetLastInputInfo;
 
+mod auth;
+mod browser_time;
+mod categories;
+mod cli;
+mod config;
+mod credentials;
+mod editors;
+mod entries;
+mod focus;
+mod git;
+mod instance;
+mod issues;
+mod ipc;
+mod pause;
+mod privacy;
+mod process;
+mod redact;
+mod report;
+mod service;
+mod shutdown;
+mod sync_policy;
+mod sync_status;
+mod tracker;
+mod urls;
+#[cfg(unix)]
+mod wayland;
+
 // -------------------- logging --------------------
 
+// Tests give themselves a data directory. It is kept per thread: a test's
+// runtime runs the tasks it spawns on the test's own thread.
+#[cfg(test)]
+thread_local! {
+    static TEST_DATA_DIR: std::cell::RefCell<Option<PathBuf>> = const { std::cell::RefCell::new(None) };
+}
+
+#[cfg(all(test, unix))]
+fn use_test_data_dir(dir: Option<PathBuf>) {
+    TEST_DATA_DIR.with(|test_dir| *test_dir.borrow_mut() = dir);
+}
+
+// %APPDATA%\Chronos on Windows, $XDG_DATA_HOME/chronos (~/.local/share/chronos)
+// elsewhere. Never the working directory, which depends on how we were started.
 fn get_app_data_dir() -> PathBuf {
-    let mut path = std::env::var("APPDATA")
+    #[cfg(test)]
+    if let Some(dir) = TEST_DATA_DIR.with(|test_dir| test_dir.borrow().clone()) {
+        return dir;
+    }
+    #[cfg(windows)]
+    let path = std::env::var_os("APPDATA")
+        .map(PathBuf::from)
+        .or_else(|| git::home_dir().map(|home| home.join("AppData").join("Roaming")))
+        .map(|dir| dir.join("Chronos"));
+    #[cfg(not(windows))]
+    let path = std::env::var_os("XDG_DATA_HOME")
         .map(PathBuf::from)
-        .unwrap_or_else(|_| PathBuf::from("."));
-    path.push("Chronos");
+        .filter(|dir| dir.is_absolute())
+        .or_else(|| git::home_dir().map(|home| home.join(".local").join("share")))
+        .map(|dir| dir.join("chronos"));
+    // no home directory at all, e.g. a bare service account
+    let path = path.unwrap_or_else(|| std::env::temp_dir().join(format!("chronos-{}", whoami::username())));
     std::fs::create_dir_all(&path).ok();
     path
 }
 
-fn log_line(line: &str) {
-    let now = Local::now();
+// Milliseconds, so short focus switches keep their real length
+const LOG_TIMESTAMP_FORMAT: &str = "%Y-%m-%d %H:%M:%S%.3f";
+
+fn append_log(stamped: &str) {
+    // not into the user's log
+    if cfg!(test) {
+        eprintln!("{}", stamped);
+        return;
+    }
     let log_path = get_app_data_dir().join("activity_log.txt");
     let mut file = OpenOptions::new()
         .create(true)
         .append(true)
         .open(log_path)
         .unwrap();
-    let _ = writeln!(file, "{} - {}", now.format("%Y-%m-%d %H:%M:%S"), line);
+    let _ = writeln!(file, "{}", stamped);
+}
+
+fn log_line(line: &str) {
+    append_log(&format!("{} - {}", Local::now().format(LOG_TIMESTAMP_FORMAT), line));
+}
+
+// Write an activity line and keep it in the tracker's recent events
+fn log_activity(tracker: &tracker::Tracker, line: &str) {
+    let stamped = format!("{} - {}", Local::now().format(LOG_TIMESTAMP_FORMAT), line);
+    append_log(&stamped);
+    if let Some(entry) = parse_log_line(&stamped) {
+        tracker.record(entry);
+    }
+}
+
+// Logged instead of a window the exclusion rules drop
+const EXCLUDED_WINDOW_EVENT: &str = "window_excluded";
+
+// Tracker events ("tracking_paused", ...) end the current window session
+fn log_tracker_event(tracker: &tracker::Tracker, event: &str) {
+    log_activity(tracker, &format!("Tracker event: {}", event));
 }
 
 // -------------------- idle detection --------------------
 // Return milliseconds since last user input (keyboard/mouse)
+#[cfg(windows)]
 #[allow(non_snake_case)]
 #[repr(C)]
 struct LASTINPUTINFO {
     cbSize: u32,
     dwTime: u32,
 }
+#[cfg(windows)]
 fn idle_ms() -> u32 {
     unsafe {
         let mut li = LASTINPUTINFO {
@@ -67,7 +154,8 @@ fn idle_ms() -> u32 {
 
 // -------------------- foreground window --------------------
 
-fn active_window_title_and_process() -> Option<(String, String)> {
+#[cfg(windows)]
+fn active_window_title_and_process() -> Option<focus::Window> {
     unsafe {
         let hwnd: HWND = GetForegroundWindow();
         if hwnd.is_invalid() {
@@ -79,42 +167,67 @@ fn active_window_title_and_process() -> Option<(String, String)> {
         let len = GetWindowTextW(hwnd, &mut title_buf);
         let title = String::from_utf16_lossy(&title_buf[..len as usize]).trim().to_string();
 
-        // PID -> exe name
+        // PID -> exe name, else the window class (see process)
         let mut pid: u32 = 0;
         GetWindowThreadProcessId(hwnd, Some(&mut pid));
-        let exe = process_name_from_pid(pid).unwrap_or_else(|| "Unknown".to_string());
+        let process = match pid {
+            0 => "Unknown".to_string(),
+            pid => process_name_from_pid(pid)
+                .or_else(|| window_class(hwnd))
+                .unwrap_or_else(|| process::INACCESSIBLE.to_string()),
+        };
 
-        Some((title, exe))
+        Some(focus::Window { title, process, pid: Some(pid).filter(|pid| *pid != 0) })
     }
 }
 
-// Get process exe name using Win32 APIs (no extra crate)
+#[cfg(windows)]
+fn window_class(hwnd: HWND) -> Option<String> {
+    let mut buf = [0u16; 256];
+    let len = unsafe { GetClassNameW(hwnd, &mut buf) };
+    (len > 0).then(|| String::from_utf16_lossy(&buf[..len as usize]))
+}
+
+// Executable name: from the full path when the process can be opened, from a
+// process snapshot when it can't (see process)
+#[cfg(windows)]
 fn process_name_from_pid(pid: u32) -> Option<String> {
-    unsafe {
-        if pid == 0 { return None; }
-        let handle_result = OpenProcess(
-            PROCESS_QUERY_INFORMATION | PROCESS_VM_READ,
-            false,
-            pid
-        );
-        
-        let handle: HANDLE = match handle_result {
-            Ok(h) => h,
-            Err(_) => return None, // Can't access this process - skip it
-        };
+    if pid == 0 {
+        return None;
+    }
+    process::image_path(pid)
+        .and_then(|path| path.rsplit('\\').next().map(str::to_string))
+        .or_else(|| process::snapshot_name(pid))
+}
 
-        // buffer for exe name
-        let mut buf = [0u16; 260];
-        let len = GetModuleBaseNameW(handle, None, &mut buf);
-        let _ = CloseHandle(handle);
-        if len > 0 {
-            Some(String::from_utf16_lossy(&buf[..len as usize]).to_string())
-        } else {
-            None
+// Nothing to poll: X11 and Wayland sessions report focus as events (see focus)
+#[cfg(unix)]
+fn active_window_title_and_process() -> Option<focus::Window> {
+    None
+}
+
+// The executable's file name, like on Windows. Other users' processes (e.g.
+// root's) only show their command name, which the kernel cuts at 15 bytes.
+#[cfg(target_os = "linux")]
+fn process_name_from_pid(pid: u32) -> Option<String> {
+    match fs::read_link(format!("/proc/{}/exe", pid)) {
+        Ok(exe) => {
+            let name = exe.file_name()?.to_string_lossy().to_string();
+            // the binary was replaced (e.g. updated) while running
+            Some(name.trim_end_matches(" (deleted)").to_string())
+        }
+        Err(_) => {
+            let comm = fs::read_to_string(format!("/proc/{}/comm", pid)).ok()?;
+            Some(comm.trim_end().to_string()).filter(|name| !name.is_empty())
         }
     }
 }
 
+#[cfg(all(unix, not(target_os = "linux")))]
+fn process_name_from_pid(_pid: u32) -> Option<String> {
+    None
+}
+
 // -------------------- browser history helpers --------------------
 
 fn chrome_history_path() -> Option<PathBuf> {
@@ -232,9 +345,113 @@ fn read_recent_firefox_visits(history_db: &Path, since_unix: i64, limit: i64) ->
     Ok(out)
 }
 
+// Browsers keep their history locked, so it is copied before reading. Focus
+// and title changes can wake the main loop several times a second; each
+// browser's history is copied at most once per HISTORY_INTERVAL.
+const HISTORY_INTERVAL: Duration = Duration::from_secs(5);
+const BROWSER_FAMILIES: [&str; 2] = ["Chromium", "Firefox"];
+
+// The most recently visited page whose title starts a window title
+// ("<page> - Google Chrome")
+fn page_url_query(family: &str) -> &'static str {
+    match family {
+        "Chromium" => "SELECT url FROM urls WHERE title != '' AND substr(?1, 1, length(title)) = title ORDER BY last_visit_time DESC LIMIT 1",
+        _ => "SELECT url FROM moz_places WHERE title != '' AND substr(?1, 1, length(title)) = title ORDER BY last_visit_date DESC LIMIT 1",
+    }
+}
+
+struct HistoryReader {
+    // newest visit logged so far (unix seconds), per browser family
+    last_seen: HashMap<&'static str, i64>,
+    // when the history was last copied, and the copy
+    copies: HashMap<&'static str, (Instant, Option<PathBuf>)>,
+    // the last page found by page_url: (window title, URL)
+    last_page: Option<(String, String)>,
+}
+
+impl HistoryReader {
+    fn new() -> HistoryReader {
+        let now = chrono::Utc::now().timestamp();
+        HistoryReader { last_seen: BROWSER_FAMILIES.iter().map(|family| (*family, now)).collect(), copies: HashMap::new(), last_page: None }
+    }
+
+    // Skip history written while paused or private
+    fn skip_to_now(&mut self) {
+        let now = chrono::Utc::now().timestamp();
+        self.last_seen.values_mut().for_each(|seen| *seen = now);
+    }
+
+    // A copy of the browser's history, at most HISTORY_INTERVAL old
+    fn copy(&mut self, family: &'static str) -> Option<PathBuf> {
+        if let Some((_, copy)) = self.copies.get(family).filter(|(at, _)| at.elapsed() < HISTORY_INTERVAL) {
+            return copy.clone();
+        }
+        let src = match family {
+            "Chromium" => chrome_history_path().or_else(edge_history_path).or_else(brave_history_path),
+            _ => firefox_history_path(),
+        };
+        let copy = src.and_then(|src| copy_history_to_temp(&src, &format!("chronos_{}_history_copy.sqlite", family.to_lowercase())));
+        self.copies.insert(family, (Instant::now(), copy.clone()));
+        copy
+    }
+
+    // The URL of the page a browser window shows, so domain exclusions cover
+    // its title too
+    fn page_url(&mut self, exe: &str, title: &str) -> Option<String> {
+        let family = browser_time::browser_family(exe)?;
+        if let Some((_, url)) = self.last_page.as_ref().filter(|(last, _)| last == title) {
+            return Some(url.clone());
+        }
+        // not found may just mean the browser hasn't saved the visit yet
+        let copy = self.copy(family)?;
+        let url = Connection::open(&copy)
+            .and_then(|conn| conn.query_row(page_url_query(family), [title], |row| row.get::<_, String>(0)))
+            .ok()?;
+        self.last_page = Some((title.to_string(), url.clone()));
+        Some(url)
+    }
+
+    // Log new history visits for the focused browser, if it is one we can read
+    fn record_visits(&mut self, exe: &str, privacy: &privacy::Privacy, tracker: &tracker::Tracker) {
+        let Some(family) = browser_time::browser_family(exe) else {
+            return;
+        };
+        let Some(copy) = self.copy(family) else {
+            return;
+        };
+        let last_seen = self.last_seen.entry(family).or_insert(0);
+        let visits = match family {
+            "Chromium" => read_recent_chromium_visits(&copy, *last_seen, 20),
+            _ => read_recent_firefox_visits(&copy, *last_seen, 20),
+        };
+        for (url, title, ts) in visits.unwrap_or_default().iter().rev() {
+            if *ts > *last_seen {
+                *last_seen = *ts;
+                let dt = chrono::DateTime::from_timestamp(*ts, 0)
+                    .unwrap_or_else(|| chrono::DateTime::from_timestamp(0, 0).unwrap())
+                    .naive_utc();
+                if let Some((url, title)) = privacy.visit(url, title, Some(exe)) {
+                    log_activity(tracker, &format!("Browser ({}) visit: {} | {} | {}", family, dt, title, url));
+                }
+            }
+        }
+    }
+}
+
+// The copies hold browsing history; don't leave them behind
+impl Drop for HistoryReader {
+    fn drop(&mut self) {
+        for (_, copy) in self.copies.values() {
+            if let Some(copy) = copy {
+                let _ = fs::remove_file(copy);
+            }
+        }
+    }
+}
+
 // -------------------- sync structures --------------------
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 struct LogEntry {
     timestamp: String,
     #[serde(rename = "type")]
@@ -247,59 +464,37 @@ struct SyncRequest {
     logs: Vec<LogEntry>,
 }
 
-// -------------------- sync functions --------------------
+// The server answered 401: the token expired or was revoked
+#[derive(Debug)]
+struct TokenRejected;
 
-fn read_token_from_user() -> String {
-    println!("🚀 Welcome to Chronos!");
-    println!("Setting up your account for the first time...");
-    println!();
-    
-    // Open the OAuth page in browser
-    let auth_url = "https://chronos-red-five.vercel.app/auth/signin?source=desktop";
-    println!("Opening your browser for authentication...");
-    
-    #[cfg(windows)]
-    {
-        std::process::Command::new("cmd")
-            .args(&["/c", "start", auth_url])
-            .spawn()
-            .ok();
+impl std::fmt::Display for TokenRejected {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "the server rejected the sync token")
     }
-    
-    println!("✅ Browser opened! Please:");
-    println!("1. Sign in with Google or GitHub");
-    println!("2. Go to 'Get Sync Token' in your dashboard");
-    println!("3. Copy your sync token");
-    println!("4. Paste it here");
-    println!();
-    print!("Enter your sync token: ");
-    io::stdout().flush().unwrap();
-    
-    let mut token = String::new();
-    io::stdin().read_line(&mut token).expect("Failed to read token");
-    token.trim().to_string()
 }
 
-fn load_token() -> Option<String> {
-    let token_path = get_app_data_dir().join("sync_token.txt");
-    match std::fs::read_to_string(token_path) {
-        Ok(token) => Some(token.trim().to_string()),
-        Err(_) => None,
-    }
-}
+impl std::error::Error for TokenRejected {}
+
+// -------------------- sync functions --------------------
 
-fn save_token(token: &str) {
-    let token_path = get_app_data_dir().join("sync_token.txt");
-    let _ = std::fs::write(token_path, token);
+async fn save_token(token: &str) {
+    match credentials::save_token(token).await {
+        Ok(backend) => log_line(&format!("Sync token stored in the {}", backend.name())),
+        Err(e) => {
+            eprintln!("Could not store the sync token: {}", e);
+            log_line(&format!("Could not store the sync token: {}", e));
+        }
+    }
 }
 
-async fn sync_logs_to_server(logs: Vec<LogEntry>, token: &str) -> Result<(), Box<dyn std::error::Error>> {
+async fn sync_logs_to_server(logs: Vec<LogEntry>, token: &str, server_url: &str) -> Result<(), Box<dyn std::error::Error>> {
     let client = reqwest::Client::new();
     
     let sync_request = SyncRequest { logs };
     
     let response = client
-        .post("https://chronos-red-five.vercel.app/api/sync")
+        .post(format!("{}/api/sync", server_url))
         .header("Authorization", format!("Bearer {}", token))
         .header("Content-Type", "application/json")
         .json(&sync_request)
@@ -309,25 +504,77 @@ async fn sync_logs_to_server(logs: Vec<LogEntry>, token: &str) -> Result<(), Box
     if response.status().is_success() {
         let result: serde_json::Value = response.json().await?;
         println!("Sync successful: {}", result.get("message").unwrap_or(&serde_json::Value::String("Done".to_string())));
+    } else if response.status() == reqwest::StatusCode::UNAUTHORIZED {
+        return Err(Box::new(TokenRejected));
     } else {
         eprintln!("Sync failed: {}", response.status());
+        return Err(format!("server returned {}", response.status()).into());
     }
 
     Ok(())
 }
 
+// Re-apply the privacy filters to entries read back from the log, so lines
+// written before the settings changed are not synced verbatim
+fn apply_privacy(mut entry: LogEntry, privacy: &privacy::Privacy) -> Option<LogEntry> {
+    if let serde_json::Value::Object(data) = &mut entry.data {
+        let field = |key: &str| data.get(key).and_then(|v| v.as_str()).unwrap_or("").to_string();
+        match entry.log_type.as_str() {
+            "window" => {
+                // the page's URL isn't logged, so domain rules only apply
+                // to titles when they are captured
+                let decision = privacy.window_decision(&field("windowTitle"), &field("processName"), None);
+                let Some((title, process)) = privacy.record_window(decision, &field("windowTitle"), &field("processName")) else {
+                    // keep the boundary, or the time goes to the window before
+                    let mut data = serde_json::Map::new();
+                    data.insert("event".to_string(), serde_json::Value::String(EXCLUDED_WINDOW_EVENT.to_string()));
+                    return Some(LogEntry { timestamp: entry.timestamp, log_type: "tracker".to_string(), data: serde_json::Value::Object(data) });
+                };
+                let details = data
+                    .contains_key("pid")
+                    .then(|| serde_json::from_value::<process::ProcessInfo>(serde_json::Value::Object(data.clone())).ok())
+                    .flatten()
+                    .and_then(|info| privacy.record_process(decision, info));
+                for key in process::DETAIL_KEYS {
+                    data.remove(key);
+                }
+                if let Some(serde_json::Value::Object(details)) = details.and_then(|info| serde_json::to_value(info).ok()) {
+                    data.extend(details);
+                }
+                data.insert("windowTitle".to_string(), serde_json::Value::String(title));
+                data.insert("processName".to_string(), serde_json::Value::String(process));
+            }
+            "browser" => {
+                let (url, title) = privacy.visit(&field("url"), &field("browserTitle"), None)?;
+                data.insert("url".to_string(), serde_json::Value::String(url));
+                data.insert("browserTitle".to_string(), serde_json::Value::String(title));
+            }
+            _ => {}
+        }
+    }
+    Some(entry)
+}
+
 fn parse_log_line(line: &str) -> Option<LogEntry> {
     // Parse format: "2025-09-02 13:02:55 - Active window: 'Title' (proc: App.exe)"
-    let parts: Vec<&str> = line.splitn(3, " - ").collect();
-    if parts.len() < 3 {
-        return None;
-    }
+    let (timestamp, content) = line.split_once(" - ")?;
+    let timestamp = timestamp.trim();
+    // not trimmed at the end: a private visit's line ends in " | " (no URL)
+    let content = content.trim_start();
 
-    let timestamp = parts[0].trim();
-    let content = parts[2].trim();
+    if let Some(event) = content.strip_prefix("Tracker event: ") {
+        let mut data = serde_json::Map::new();
+        data.insert("event".to_string(), serde_json::Value::String(event.trim().to_string()));
+        return Some(LogEntry {
+            timestamp: timestamp.to_string(),
+            log_type: "tracker".to_string(),
+            data: serde_json::Value::Object(data),
+        });
+    }
 
     if content.starts_with("Active window:") {
-        // Parse window activity
+        // Parse window activity; process details (see process) come last
+        let (content, details) = process::split_details(content);
         if let Some(start) = content.find("'") {
             if let Some(end) = content.rfind("'") {
                 let title = &content[start + 1..end];
@@ -338,6 +585,7 @@ fn parse_log_line(line: &str) -> Option<LogEntry> {
                         let mut data = serde_json::Map::new();
                         data.insert("windowTitle".to_string(), serde_json::Value::String(title.to_string()));
                         data.insert("processName".to_string(), serde_json::Value::String(process_name.to_string()));
+                        data.extend(details.into_iter().flatten());
 
                         return Some(LogEntry {
                             timestamp: timestamp.to_string(),
@@ -353,14 +601,24 @@ fn parse_log_line(line: &str) -> Option<LogEntry> {
         let parts: Vec<&str> = content.split(" | ").collect();
         if parts.len() >= 3 {
             let browser_type = if content.contains("Firefox") { "Firefox" } else { "Chromium" };
-            let title = parts[1].trim();
-            let url = parts[2].trim();
+            // the title may itself contain " | ", the URL can't
+            let title = parts[1..parts.len() - 1].join(" | ");
+            let title = title.trim();
+            let url = parts[parts.len() - 1].trim();
             
             let mut data = serde_json::Map::new();
             data.insert("browserType".to_string(), serde_json::Value::String(browser_type.to_string()));
             data.insert("browserTitle".to_string(), serde_json::Value::String(title.to_string()));
             data.insert("url".to_string(), serde_json::Value::String(url.to_string()));
 
+            // Visit time from the history db (UTC), e.g. "... visit: 2025-09-02 08:02:55"
+            if let Some((_, visited)) = parts[0].split_once("visit: ") {
+                if let Ok(dt) = chrono::NaiveDateTime::parse_from_str(visited.trim(), "%Y-%m-%d %H:%M:%S") {
+                    let visited_at = dt.and_utc().to_rfc3339_opts(chrono::SecondsFormat::Secs, true);
+                    data.insert("visitedAt".to_string(), serde_json::Value::String(visited_at));
+                }
+            }
+
             return Some(LogEntry {
                 timestamp: timestamp.to_string(),
                 log_type: "browser".to_string(),
@@ -372,31 +630,65 @@ fn parse_log_line(line: &str) -> Option<LogEntry> {
     None
 }
 
-async fn sync_local_logs(token: &str) -> Result<(), Box<dyn std::error::Error>> {
+// Lines written by the window loop and the browser readers, as opposed to
+// status messages
+fn is_activity_line(line: &str) -> bool {
+    let content = line.split_once(" - ").map(|(_, c)| c.trim()).unwrap_or("");
+    content.starts_with("Active window:") || (content.starts_with("Browser (") && content.contains("visit:"))
+}
+
+// Activity entries from the local log, with the privacy filters applied
+fn read_log_entries(privacy: &privacy::Privacy) -> std::io::Result<Vec<LogEntry>> {
     let log_path = get_app_data_dir().join("activity_log.txt");
     if !log_path.exists() {
-        return Ok(());
+        return Ok(Vec::new());
     }
 
     let content = std::fs::read_to_string(&log_path)?;
-    let lines: Vec<&str> = content.lines().collect();
-    
     let mut log_entries = Vec::new();
-    
-    for line in lines {
-        if let Some(entry) = parse_log_line(line) {
-            log_entries.push(entry);
-        } else {
-            // Debug: log lines that couldn't be parsed
-            if line.trim().len() > 0 && !line.contains("No new log entries") && !line.contains("Starting main") && !line.contains("Main loop iteration") {
-                log_line(&format!("Could not parse log line: {}", line));
-            }
+    for line in content.lines() {
+        match parse_log_line(line) {
+            Some(entry) => log_entries.extend(apply_privacy(entry, privacy)),
+            // Status lines are expected here; only report activity we failed to read
+            None if is_activity_line(line) => log_line(&format!("Could not parse log line: {}", line)),
+            None => {}
         }
     }
+    Ok(log_entries)
+}
+
+async fn sync_local_logs(
+    token: &str,
+    server_url: &str,
+    privacy: &privacy::Privacy,
+    issues: &issues::IssueDetector,
+    categories: &categories::Categoriser,
+    policy: &sync_policy::SyncPolicy,
+    status: &mut sync_status::SyncStatus,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut log_entries = read_log_entries(privacy)?;
 
     if !log_entries.is_empty() {
+        // Attribute focused browser time to the visited pages/domains
+        browser_time::annotate_browser_entries(&mut log_entries);
+        issues.tag(&mut log_entries);
+        categories.tag(&mut log_entries);
+        let top_domains: Vec<String> = browser_time::domain_durations(&log_entries)
+            .into_iter()
+            .take(5)
+            .map(|(domain, secs)| format!("{} {}m", domain, secs / 60))
+            .collect();
+        if !top_domains.is_empty() {
+            log_line(&format!("Browser time by domain: {}", top_domains.join(", ")));
+        }
+
+        // Only send what the sync level allows; the local log keeps everything
+        let apps_sent_until = status.apps_synced_until.as_deref().and_then(entries::parse_timestamp);
+        let (log_entries, apps_synced_until) = policy.minimise(log_entries, apps_sent_until, Local::now().naive_local());
+
         let entry_count = log_entries.len();
-        sync_logs_to_server(log_entries, token).await?;
+        sync_logs_to_server(log_entries, token, server_url).await?;
+        status.apps_synced_until = Some(entries::format_timestamp(apps_synced_until));
         log_line(&format!("Synced {} log entries to server", entry_count));
     } else {
         log_line("No new log entries to sync");
@@ -405,6 +697,113 @@ async fn sync_local_logs(token: &str) -> Result<(), Box<dyn std::error::Error>>
     Ok(())
 }
 
+// -------------------- sync task --------------------
+
+fn start_relogin(server_url: &str) -> tokio::task::JoinHandle<Result<String, String>> {
+    log_line("Asking you to sign in again in the browser...");
+    tokio::spawn(auth::renew_in_browser(server_url.to_string()))
+}
+
+// Sync every 30 seconds. When the server rejects the token, syncing pauses
+// (the main loop keeps recording) until a new token arrives, either from the
+// browser sign-in started here or from `chronos login`. The credential store
+// is only read again when asked to sync (`chronos login` does once it has
+// stored a token), not on every pass: reading the keyring can show its
+// unlock prompt.
+async fn run_sync_task(
+    mut token: String,
+    server_url: String,
+    privacy: Arc<privacy::Privacy>,
+    issues: Arc<issues::IssueDetector>,
+    categories: Arc<categories::Categoriser>,
+    policy: sync_policy::SyncPolicy,
+    mut sync_requests: tokio::sync::mpsc::Receiver<tracker::SyncRequest>,
+) {
+    let mut status = sync_status::SyncStatus::load();
+    let mut relogin = if status.is_paused() { Some(start_relogin(&server_url)) } else { None };
+    let mut last_expiry_check: Option<chrono::NaiveDate> = None;
+
+    let mut sync_interval = tokio::time::interval(Duration::from_secs(30)); // Sync every 30 seconds for testing
+    loop {
+        // `chronos sync --now` gets an answer once its sync is done
+        let reply = tokio::select! {
+            _ = sync_interval.tick() => None,
+            Some(reply) = sync_requests.recv() => Some(reply),
+        };
+
+        if reply.is_some() {
+            if let Some(stored) = credentials::load_token().await.filter(|stored| *stored != token) {
+                token = stored;
+                if status.is_paused() {
+                    status.token_rejected_at = None;
+                    status.save();
+                    log_line("Signed in again, resuming sync");
+                }
+            }
+        }
+
+        if status.is_paused() {
+            let mut new_token = None;
+            if relogin.as_ref().is_some_and(|handle| handle.is_finished()) {
+                match relogin.take().unwrap().await {
+                    Ok(Ok(renewed)) => {
+                        save_token(&renewed).await;
+                        new_token = Some(renewed);
+                    }
+                    Ok(Err(e)) => log_line(&format!("Browser sign-in failed ({}). Run `chronos login` to resume syncing.", e)),
+                    Err(e) => log_line(&format!("Sign-in task failed: {}", e)),
+                }
+            }
+            match new_token {
+                Some(renewed) => {
+                    token = renewed;
+                    status.token_rejected_at = None;
+                    status.save();
+                    log_line("Signed in again, resuming sync");
+                }
+                None => {
+                    if let Some(reply) = reply {
+                        let _ = reply.send(Err("sync is paused until you sign in again (`chronos login`)".to_string()));
+                    }
+                    continue;
+                }
+            }
+        }
+
+        // Warn once a day when the token is about to run out
+        let today = Local::now().date_naive();
+        if last_expiry_check != Some(today) {
+            last_expiry_check = Some(today);
+            if let Some(warning) = auth::expiry_warning(&token) {
+                log_line(&warning);
+            }
+        }
+
+        let outcome = match sync_local_logs(&token, &server_url, &privacy, &issues, &categories, &policy, &mut status).await {
+            Ok(()) => {
+                status.last_sync = Some(sync_status::now());
+                status.last_error = None;
+                Ok(())
+            }
+            Err(e) => {
+                let rejected = e.is::<TokenRejected>();
+                log_line(&format!("Sync error: {}", e));
+                status.last_error = Some(e.to_string());
+                if rejected {
+                    log_line("Sync paused until you sign in again. Activity is still recorded locally.");
+                    status.token_rejected_at = Some(sync_status::now());
+                    relogin = Some(start_relogin(&server_url));
+                }
+                Err(e.to_string())
+            }
+        };
+        status.save();
+        if let Some(reply) = reply {
+            let _ = reply.send(outcome);
+        }
+    }
+}
+
 // -------------------- main loop --------------------
 
 #[tokio::main]
@@ -422,35 +821,63 @@ async fn main() {
         log_line(&format!("PANIC at {}: {}", location, msg));
     }));
 
+    let cli = cli::Cli::parse();
+    let config = config::load_config();
+    let server_url = config.server_url();
+
+    // Older versions kept the token in plaintext
+    credentials::migrate_plaintext_token().await;
+
+    if let Err(e) = cli::execute(cli.command.unwrap_or(cli::Command::Run), &config, &server_url).await {
+        eprintln!("Error: {}", e);
+        std::process::exit(1);
+    }
+}
+
+// Record activity until the process is killed (`chronos run`, the default)
+async fn run_tracker(config: &config::Config, server_url: &str) {
+    // A second `chronos` (autostart plus a manual start) hands over to the first
+    let _lock = match instance::InstanceLock::acquire() {
+        Ok(lock) => lock,
+        Err(owner) => {
+            cli::report_running_instance(&owner).await;
+            thread::sleep(Duration::from_secs(3));
+            return;
+        }
+    };
+
     // Handle token setup first (with visible console)
-    let token = match load_token() {
+    let token = match credentials::load_token().await {
         Some(token) => {
             println!("✅ Chronos is running in the background");
-            println!("Dashboard: https://chronos-red-five.vercel.app/dashboard");
+            println!("Dashboard: {}/dashboard", server_url);
             println!("Press Ctrl+C to stop or simply close this window");
+            let pause_state = pause::PauseState::load_active();
+            if pause_state.is_paused() {
+                // a pause outlives restarts; say so rather than look broken
+                let what = if pause_state.mode == pause::PauseMode::Private { "Private mode is on" } else { "Tracking is paused" };
+                println!("⏸️  {}{}. Run `chronos resume` to track normally.", what, pause::describe_until(&pause_state));
+            }
+            if sync_status::SyncStatus::load().is_paused() {
+                println!("⚠️  Sync is paused: the server rejected your token. Sign in again to resume.");
+            } else if let Some(warning) = auth::expiry_warning(&token) {
+                println!("⚠️  {}", warning);
+            }
             
             // Don't hide console immediately - wait until after setup
             token
         }
         None => {
             // Keep console visible for first-time setup
-            println!("🎉 Welcome to Chronos Activity Tracker!");
-            println!("Opening browser for authentication...");
-            
-            // Open browser to signin page
-            let signin_url = "https://chronos-red-five.vercel.app/auth/signin?source=desktop";
-            let _ = Command::new("cmd")
-                .args(["/c", "start", signin_url])
-                .spawn();
-            
-            println!();
-            println!("Please complete the authentication in your browser, then:");
-            println!("1. Visit: https://chronos-red-five.vercel.app/token");
-            println!("2. Copy your token and paste it below");
-            println!();
-            
-            let token = read_token_from_user();
-            save_token(&token);
+            let token = match auth::sign_in(server_url).await {
+                Ok(token) => token,
+                Err(e) => {
+                    eprintln!("Sign-in failed: {}. Run `chronos login` to try again.", e);
+                    thread::sleep(Duration::from_secs(3));
+                    return;
+                }
+            };
+            save_token(&token).await;
             println!("✅ Setup complete! Chronos is now running in the background.");
             println!("You can minimize this window. Check your dashboard for activity data.");
             
@@ -463,103 +890,183 @@ async fn main() {
 
     log_line("Chronos started");
 
-    // Clone token for sync task
-    let sync_token = token.clone();
-    
+    let privacy = Arc::new(privacy::Privacy::from_config(config));
+    let app_rules = process::AppRules::from_config(config);
+    let title_parsers = editors::TitleParsers::from_config(config);
+    let issue_detector = Arc::new(issues::IssueDetector::from_config(config));
+    let categoriser = Arc::new(categories::Categoriser::from_config(config));
+
+    let sync_policy = sync_policy::SyncPolicy::from_config(config);
+
+    // Live state for the control API
+    let (tracker, sync_requests) = tracker::Tracker::new(sync_status::now());
+    tokio::spawn(ipc::serve(tracker.clone()));
+
     // Spawn periodic sync task with error handling
-    tokio::spawn(async move {
-        let mut sync_interval = tokio::time::interval(Duration::from_secs(30)); // Sync every 30 seconds for testing
-        loop {
-            sync_interval.tick().await;
-            if let Err(e) = sync_local_logs(&sync_token).await {
-                log_line(&format!("Sync error: {}", e));
-            }
-        }
-    });
+    tokio::spawn(run_sync_task(
+        token.clone(),
+        server_url.to_string(),
+        Arc::clone(&privacy),
+        Arc::clone(&issue_detector),
+        Arc::clone(&categoriser),
+        sync_policy,
+        sync_requests,
+    ));
 
-    // track last seen times to avoid duplicate browser logs
-    let mut last_seen_chromium_unix: i64 = chrono::Utc::now().timestamp();
-    let mut last_seen_firefox_unix: i64 = chrono::Utc::now().timestamp();
+    // track last seen visits to avoid duplicate browser logs
+    let mut history = HistoryReader::new();
 
     // track last activity types to reduce logging noise
     let mut last_window: Option<(String, String)> = None;
+    let mut last_activity = pause::Activity::Tracking;
 
     // Simplified approach: assume user is always active for now
     // This will be improved later once we get the basic tracking stable
     log_line("Starting main activity tracking loop (simplified mode)...");
 
+    // focus changes wake the main loop right away
+    focus::start(tracker.wake.clone());
+
+    // tell systemd (when it started us) that we're up
+    service::notify("READY=1");
+
     // Main loop with comprehensive error handling
+    let stop = shutdown::requested();
+    tokio::pin!(stop);
     let mut loop_count = 0;
-    loop {
+    let reason = loop {
         loop_count += 1;
         if loop_count % 12 == 1 { // Log every minute (12 * 5 seconds)
             log_line(&format!("Main loop iteration: {}", loop_count));
         }
+        service::notify("WATCHDOG=1");
         
-        match tokio::time::timeout(Duration::from_secs(10), async {
+        let iteration = tokio::time::timeout(Duration::from_secs(10), async {
+            // `chronos pause`/`chronos private` can change this while we run
+            let activity = pause::PauseState::load_active().activity();
+            if activity != last_activity {
+                log_tracker_event(&tracker, match activity {
+                    pause::Activity::Tracking => "tracking_resumed",
+                    pause::Activity::Paused => "tracking_paused",
+                    pause::Activity::Private => "private_mode_started",
+                });
+                // log the focused window again on resume so a new session starts
+                last_window = None;
+                tracker.set_session(None);
+                last_activity = activity;
+            }
+            if activity != pause::Activity::Tracking {
+                history.skip_to_now();
+            }
+
             // For now, assume user is always active to test the basic functionality
-            let active = true;
+            let active = activity != pause::Activity::Paused;
 
             if active {
-                if let Some((title, exe)) = active_window_title_and_process() {
-                    // only log when window changes
-                    if Some((title.clone(), exe.clone())) != last_window {
-                        log_line(&format!("Active window: '{}' (proc: {})", title, exe));
-                        last_window = Some((title.clone(), exe.clone()));
-                    }
+                if let Some(window) = focus::active_window() {
+                    let (title, exe) = (&window.title, &window.process);
+                    // exclusion lists decide what (if anything) gets recorded;
+                    // private mode keeps the time but nothing else
+                    let decision = if activity == pause::Activity::Private {
+                        privacy::Decision::Private
+                    } else {
+                        privacy.window_decision(title, exe, history.page_url(exe, title).as_deref())
+                    };
+                    let recorded = privacy.record_window(decision, title, exe);
 
-                    let exe_lower = exe.to_lowercase();
-                    // Chromium family
-                    if exe_lower.contains("chrome") || exe_lower.contains("msedge") || exe_lower.contains("brave") {
-                        if let Some(src) = chrome_history_path()
-                            .or_else(edge_history_path)
-                            .or_else(brave_history_path)
-                        {
-                            if let Some(copy) = copy_history_to_temp(&src, "chronos_chromium_history_copy.sqlite") {
-                                if let Ok(visits) = read_recent_chromium_visits(&copy, last_seen_chromium_unix, 20) {
-                                    for (url, title, ts) in visits.iter().rev() {
-                                        if *ts > last_seen_chromium_unix {
-                                            last_seen_chromium_unix = *ts;
-                                            let dt = chrono::DateTime::from_timestamp(*ts, 0)
-                                                .unwrap_or_else(|| chrono::DateTime::from_timestamp(0, 0).unwrap())
-                                                .naive_utc();
-                                            log_line(&format!("Browser (Chromium) visit: {} | {} | {}", dt, title, url));
-                                        }
-                                    }
-                                }
-                                let _ = fs::remove_file(copy);
-                            }
+                    // only log when window changes
+                    if recorded != last_window {
+                        // path, command line, app identity, terminal job, editor
+                        // title and repository, read once per change
+                        let details = window
+                            .pid
+                            .and_then(process::inspect)
+                            .map(|info| process::add_terminal_job(app_rules.identify(exe, info), title))
+                            .map(|info| title_parsers.parse(exe, title, info))
+                            .map(|info| git::attribute(info, title))
+                            .and_then(|info| privacy.record_process(decision, info));
+                        match &recorded {
+                            Some((title, exe)) => log_activity(
+                                &tracker,
+                                &format!("Active window: '{}' (proc: {}){}", title, exe, process::format_details(details.as_ref())),
+                            ),
+                            // nothing about the window is kept, but its time
+                            // must not go to the window before
+                            None => log_tracker_event(&tracker, EXCLUDED_WINDOW_EVENT),
                         }
+                        tracker.set_session(recorded.as_ref().map(|(title, process)| tracker::Session {
+                            title: title.clone(),
+                            process: process.clone(),
+                            app: details.as_ref().and_then(|info| info.app.clone()),
+                            repository: details.as_ref().and_then(|info| info.repository.clone()),
+                            branch: details.as_ref().and_then(|info| info.branch.clone()),
+                            issues: issue_detector.find(title, None, details.as_ref().and_then(|info| info.branch.as_deref())),
+                            category: {
+                                // the fields the window's log entry will have
+                                let mut data = match serde_json::to_value(&details) {
+                                    Ok(serde_json::Value::Object(data)) => data,
+                                    _ => serde_json::Map::new(),
+                                };
+                                data.insert("windowTitle".to_string(), title.clone().into());
+                                data.insert("processName".to_string(), process.clone().into());
+                                categoriser.categorise("window", &data).map(|category| category.name)
+                            },
+                            since: sync_status::now(),
+                        }));
+                        last_window = recorded;
                     }
 
-                    // Firefox
-                    if exe_lower.contains("firefox") {
-                        if let Some(src) = firefox_history_path() {
-                            if let Some(copy) = copy_history_to_temp(&src, "chronos_firefox_history_copy.sqlite") {
-                                if let Ok(visits) = read_recent_firefox_visits(&copy, last_seen_firefox_unix, 20) {
-                                    for (url, title, ts) in visits.iter().rev() {
-                                        if *ts > last_seen_firefox_unix {
-                                            last_seen_firefox_unix = *ts;
-                                            let dt = chrono::DateTime::from_timestamp(*ts, 0)
-                                                .unwrap_or_else(|| chrono::DateTime::from_timestamp(0, 0).unwrap())
-                                                .naive_utc();
-                                            log_line(&format!("Browser (Firefox) visit: {} | {} | {}", dt, title, url));
-                                        }
-                                    }
-                                }
-                                let _ = fs::remove_file(copy);
-                            }
-                        }
+                    // don't read browser history while an excluded window is focused
+                    if decision == privacy::Decision::Keep {
+                        history.record_visits(exe, &privacy, &tracker);
                     }
                 }
             }
 
-            tokio::time::sleep(Duration::from_secs(5)).await;
-        }).await {
-            Ok(_) => {}, // Normal execution
-            Err(_) => {
-                log_line("Main loop timeout - continuing...");
+            // focus changes and pause/resume through the control API wake us early
+            tokio::select! {
+                _ = tokio::time::sleep(Duration::from_secs(5)) => {}
+                _ = tracker.wake.notified() => {}
             }
+        });
+        tokio::select! {
+            reason = &mut stop => break reason,
+            result = iteration => match result {
+                Ok(_) => {}, // Normal execution
+                Err(_) => {
+                    log_line("Main loop timeout - continuing...");
+                }
+            },
+        }
+    };
+
+    drop(history);
+    shut_down(&tracker, reason).await;
+}
+
+// How long the last sync may hold up stopping (Windows kills a closed console
+// after about 5 seconds)
+const FINAL_SYNC_TIMEOUT: Duration = Duration::from_secs(3);
+
+// End the session in progress, make sure the log is on disk and give the
+// sync task one bounded chance to upload it
+async fn shut_down(tracker: &tracker::Tracker, reason: &str) {
+    log_line(&format!("Stopping ({})", reason));
+    service::notify("STOPPING=1");
+    tracker.set_session(None);
+    log_tracker_event(tracker, "tracker_stopped");
+    if let Ok(file) = OpenOptions::new().append(true).open(get_app_data_dir().join("activity_log.txt")) {
+        let _ = file.sync_all();
+    }
+
+    let (reply, answer) = tokio::sync::oneshot::channel();
+    if tracker.sync_now.send(reply).await.is_ok() {
+        match tokio::time::timeout(FINAL_SYNC_TIMEOUT, answer).await {
+            Ok(Ok(Ok(()))) => log_line("Final sync done"),
+            Ok(Ok(Err(e))) => log_line(&format!("Final sync failed: {}", e)),
+            _ => log_line("Final sync didn't finish in time; the rest syncs on the next start"),
         }
     }
+    log_line("Chronos stopped");
+    shutdown::finished();
 }
@@ -0,0 +1,232 @@
+use chrono::{Duration, Local, NaiveDate, NaiveDateTime, NaiveTime};
+use serde::{Deserialize, Serialize};
+
+use crate::{get_app_data_dir, log_line};
+
+// -------------------- pause and private mode --------------------
+// `chronos pause` stops recording; `chronos private` keeps recording time
+// but only as "Private" blocks, without titles or URLs. Both last until
+// `chronos resume` or until a given time ("30m", "until tomorrow"). The state
+// lives in pause.json: the tracker checks it on every loop, so it applies to
+// a running tracker and a restart doesn't silently resume tracking.
+
+const TIMESTAMP_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum PauseMode {
+    // Record nothing
+    #[default]
+    Paused,
+    // Record time as "Private" without titles or URLs
+    Private,
+}
+
+// What the tracker should be doing right now
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Activity {
+    Tracking,
+    Paused,
+    Private,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(default, rename_all = "camelCase")]
+pub struct PauseState {
+    pub mode: PauseMode,
+    // Local "%Y-%m-%d %H:%M:%S" timestamps
+    pub paused_since: Option<String>,
+    // None lasts until resumed
+    pub paused_until: Option<String>,
+}
+
+fn pause_path() -> std::path::PathBuf {
+    get_app_data_dir().join("pause.json")
+}
+
+fn now() -> NaiveDateTime {
+    Local::now().naive_local()
+}
+
+// "30m", "2h", "1h30m", "90s", "1d"; spaces are ignored, as in
+// `chronos pause 1h 30m`
+pub fn parse_duration(text: &str) -> Option<Duration> {
+    let text = text.trim().to_lowercase();
+    let mut total = Duration::zero();
+    let mut number = String::new();
+    for c in text.chars().filter(|c| !c.is_whitespace()) {
+        if c.is_ascii_digit() {
+            number.push(c);
+            continue;
+        }
+        let n: i64 = number.parse().ok()?;
+        number.clear();
+        total += match c {
+            's' => Duration::seconds(n),
+            'm' => Duration::minutes(n),
+            'h' => Duration::hours(n),
+            'd' => Duration::days(n),
+            _ => return None,
+        };
+    }
+    // a trailing number without a unit is ambiguous
+    if !number.is_empty() || total <= Duration::zero() {
+        return None;
+    }
+    Some(total)
+}
+
+// When a pause given as text ends, relative to `from`:
+//   "30m", "for 1h30m"        - a duration
+//   "until tomorrow"          - the start of the next day
+//   "until 14:00"             - the next time the clock reads 14:00
+//   "until 2025-09-03 09:00"  - that time ("2025-09-03" alone means midnight)
+pub fn parse_until(text: &str, from: NaiveDateTime) -> Option<NaiveDateTime> {
+    let text = text.trim().to_lowercase();
+    let text = text
+        .strip_prefix("until ")
+        .or_else(|| text.strip_prefix("for "))
+        .unwrap_or(&text)
+        .trim();
+
+    if let Some(duration) = parse_duration(text) {
+        return Some(from + duration);
+    }
+    let until = match text {
+        "tomorrow" => from.date().succ_opt()?.and_hms_opt(0, 0, 0)?,
+        _ => {
+            if let Ok(time) = NaiveTime::parse_from_str(text, "%H:%M") {
+                let today = from.date().and_time(time);
+                if today > from { today } else { from.date().succ_opt()?.and_time(time) }
+            } else if let Ok(at) = NaiveDateTime::parse_from_str(text, "%Y-%m-%d %H:%M") {
+                at
+            } else {
+                NaiveDate::parse_from_str(text, "%Y-%m-%d").ok()?.and_hms_opt(0, 0, 0)?
+            }
+        }
+    };
+    (until > from).then_some(until)
+}
+
+impl PauseState {
+    pub fn load() -> PauseState {
+        std::fs::read_to_string(pause_path())
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default()
+    }
+
+    // Like load(), but a pause whose time has run out is cleared from disk
+    pub fn load_active() -> PauseState {
+        let state = PauseState::load();
+        if state.paused_since.is_some() && !state.is_paused() {
+            log_line(&format!("Pause ended at {}", state.paused_until.as_deref().unwrap_or("?")));
+            if let Err(e) = PauseState::default().save() {
+                log_line(&format!("Could not clear pause state: {}", e));
+            }
+            return PauseState::default();
+        }
+        state
+    }
+
+    pub fn save(&self) -> std::io::Result<()> {
+        std::fs::write(pause_path(), serde_json::to_string_pretty(self)?)
+    }
+
+    pub fn is_paused(&self) -> bool {
+        if self.paused_since.is_none() {
+            return false;
+        }
+        let until = self
+            .paused_until
+            .as_deref()
+            .and_then(|until| NaiveDateTime::parse_from_str(until, TIMESTAMP_FORMAT).ok());
+        match until {
+            Some(until) => now() < until,
+            None => true,
+        }
+    }
+
+    pub fn activity(&self) -> Activity {
+        match (self.is_paused(), self.mode) {
+            (false, _) => Activity::Tracking,
+            (true, PauseMode::Paused) => Activity::Paused,
+            (true, PauseMode::Private) => Activity::Private,
+        }
+    }
+
+    // Pause (or go private) now, until `until` or until resumed
+    pub fn pause(mode: PauseMode, until: Option<NaiveDateTime>) -> std::io::Result<PauseState> {
+        let state = PauseState {
+            mode,
+            paused_since: Some(now().format(TIMESTAMP_FORMAT).to_string()),
+            paused_until: until.map(|until| until.format(TIMESTAMP_FORMAT).to_string()),
+        };
+        state.save()?;
+        Ok(state)
+    }
+
+    // Returns whether tracking was paused or private
+    pub fn resume() -> std::io::Result<bool> {
+        let was_paused = PauseState::load().is_paused();
+        PauseState::default().save()?;
+        Ok(was_paused)
+    }
+}
+
+// "until 2025-09-03 09:00" for messages, empty when open-ended
+pub fn describe_until(state: &PauseState) -> String {
+    state.paused_until.as_deref().map(|until| format!(" until {}", until)).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn durations() {
+        assert_eq!(parse_duration("1h30m"), Some(Duration::minutes(90)));
+        // `chronos pause 1h 30m` joins its arguments with a space
+        assert_eq!(parse_duration("1h 30m"), Some(Duration::minutes(90)));
+        assert_eq!(parse_duration(" 2H "), Some(Duration::hours(2)));
+        assert_eq!(parse_duration("90s"), Some(Duration::seconds(90)));
+        assert_eq!(parse_duration("1d"), Some(Duration::days(1)));
+        // no unit, nothing at all, unknown units
+        for text in ["10", "0m", "0h0m", "", "m", "1w", "soon", "-5m"] {
+            assert_eq!(parse_duration(text), None, "{:?}", text);
+        }
+    }
+
+    fn at(text: &str) -> NaiveDateTime {
+        NaiveDateTime::parse_from_str(text, "%Y-%m-%d %H:%M").unwrap()
+    }
+
+    #[test]
+    fn pause_ends() {
+        let morning = at("2025-09-02 09:15");
+        let afternoon = at("2025-09-02 15:00");
+        let cases = [
+            ("30m", morning, Some(at("2025-09-02 09:45"))),
+            ("for 1h30m", morning, Some(at("2025-09-02 10:45"))),
+            ("For 1h 30m", morning, Some(at("2025-09-02 10:45"))),
+            ("until 14:00", morning, Some(at("2025-09-02 14:00"))),
+            // already past: tomorrow's
+            ("until 14:00", afternoon, Some(at("2025-09-03 14:00"))),
+            ("until 15:00", afternoon, Some(at("2025-09-03 15:00"))),
+            ("until tomorrow", afternoon, Some(at("2025-09-03 00:00"))),
+            ("until 2025-09-03", morning, Some(at("2025-09-03 00:00"))),
+            ("until 2025-09-03 09:00", morning, Some(at("2025-09-03 09:00"))),
+            // in the past
+            ("until 2025-09-01", morning, None),
+            ("until 2025-09-02 09:00", morning, None),
+            ("10", morning, None),
+            ("0m", morning, None),
+            ("until", morning, None),
+            ("until lunch", morning, None),
+            ("until 25:00", morning, None),
+        ];
+        for (text, from, expected) in cases {
+            assert_eq!(parse_until(text, from), expected, "{:?} from {}", text, from);
+        }
+    }
+}
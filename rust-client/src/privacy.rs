@@ -0,0 +1,285 @@
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+use crate::config::Config;
+use crate::log_line;
+use crate::process::{process_name, ProcessInfo};
+use crate::redact::Redactor;
+use crate::urls::{self, bare_domain, domain_of, UrlConfig};
+
+// -------------------- capture-time privacy filters --------------------
+// Everything captured by the window loop and the browser readers passes
+// through Privacy before it is written to the activity log. The same filters
+// run again at sync time so that rules added later also cover old lines.
+
+pub const PRIVATE_PLACEHOLDER: &str = "Private";
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ExclusionAction {
+    // Don't record anything
+    Drop,
+    // Record the time as a generic "Private" entry
+    #[default]
+    Private,
+}
+
+// A deny-list entry. Every criterion that is set must match, e.g.
+// { "process": "firefox.exe", "titlePattern": "Private Browsing" }.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(default, rename_all = "camelCase")]
+pub struct ExclusionRule {
+    // Process name, case-insensitive, ".exe" optional
+    pub process: Option<String>,
+    // Regex matched against the window title (or page title for visits)
+    pub title_pattern: Option<String>,
+    // Domain, also matches its subdomains
+    pub domain: Option<String>,
+    pub action: ExclusionAction,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Decision {
+    Keep,
+    Drop,
+    Private,
+}
+
+struct CompiledRule {
+    process: Option<String>,
+    title: Option<Regex>,
+    domain: Option<String>,
+    action: ExclusionAction,
+}
+
+impl CompiledRule {
+    fn compile(rule: &ExclusionRule) -> Option<CompiledRule> {
+        let title = match &rule.title_pattern {
+            Some(pattern) => match Regex::new(pattern) {
+                Ok(re) => Some(re),
+                Err(e) => {
+                    log_line(&format!("Ignoring exclusion rule with invalid title pattern '{}': {}", pattern, e));
+                    return None;
+                }
+            },
+            None => None,
+        };
+        if rule.process.is_none() && title.is_none() && rule.domain.is_none() {
+            return None;
+        }
+        Some(CompiledRule {
+            process: rule.process.as_deref().map(process_name),
+            title,
+            domain: rule.domain.as_deref().map(bare_domain),
+            action: rule.action,
+        })
+    }
+
+    // `process` and `url` are None when unknown; a rule that needs them
+    // then doesn't match
+    fn matches(&self, process: Option<&str>, title: &str, url: Option<&str>) -> bool {
+        if let Some(wanted) = &self.process {
+            if process.map(process_name).as_deref() != Some(wanted.as_str()) {
+                return false;
+            }
+        }
+        if let Some(re) = &self.title {
+            if !re.is_match(title) {
+                return false;
+            }
+        }
+        if let Some(wanted) = &self.domain {
+            let host = match url.and_then(domain_of) {
+                Some(host) => bare_domain(&host),
+                None => return false,
+            };
+            if host != *wanted && !host.ends_with(&format!(".{}", wanted)) {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+pub struct Privacy {
+    urls: UrlConfig,
+    exclusions: Vec<CompiledRule>,
+    redactor: Redactor,
+}
+
+impl Privacy {
+    pub fn from_config(config: &Config) -> Privacy {
+        Privacy {
+            urls: config.urls.clone(),
+            exclusions: config.exclusions.iter().filter_map(CompiledRule::compile).collect(),
+            redactor: Redactor::from_config(&config.redaction),
+        }
+    }
+
+    // Drop wins over Private when several rules match
+    fn decide(&self, process: Option<&str>, title: &str, url: Option<&str>) -> Decision {
+        let mut decision = Decision::Keep;
+        for rule in self.exclusions.iter().filter(|r| r.matches(process, title, url)) {
+            match rule.action {
+                ExclusionAction::Drop => return Decision::Drop,
+                ExclusionAction::Private => decision = Decision::Private,
+            }
+        }
+        decision
+    }
+
+    // `url` is the page a browser window shows, when known
+    pub fn window_decision(&self, title: &str, process: &str, url: Option<&str>) -> Decision {
+        self.decide(Some(process), title, url)
+    }
+
+    // What to record for a focused window once its decision is known. None
+    // means don't record it at all.
+    pub fn record_window(&self, decision: Decision, title: &str, process: &str) -> Option<(String, String)> {
+        match decision {
+            Decision::Keep => Some((self.redactor.redact(title), process.to_string())),
+            Decision::Private => Some((PRIVATE_PLACEHOLDER.to_string(), PRIVATE_PLACEHOLDER.to_string())),
+            Decision::Drop => None,
+        }
+    }
+
+    // Process details go with kept windows only; command lines, paths and
+    // what was read from the title are redacted like titles (tokens passed as
+    // arguments, home directories)
+    pub fn record_process(&self, decision: Decision, mut info: ProcessInfo) -> Option<ProcessInfo> {
+        if decision != Decision::Keep {
+            return None;
+        }
+        let texts = [
+            &mut info.exe_path,
+            &mut info.command_line,
+            &mut info.foreground_command,
+            &mut info.working_directory,
+            &mut info.repository_path,
+            &mut info.project,
+            &mut info.file,
+        ];
+        for text in texts {
+            *text = text.as_deref().map(|text| self.redactor.redact(text));
+        }
+        Some(info)
+    }
+
+    // Filter and normalise a browser visit, returning (url, title).
+    // `process` is the focused browser when known. Exclusions see the raw
+    // title; only what gets recorded is redacted.
+    pub fn visit(&self, url: &str, title: &str, process: Option<&str>) -> Option<(String, String)> {
+        match self.decide(process, title, Some(url)) {
+            Decision::Keep => {
+                let (url, title) = urls::sanitize_visit(url, title, &self.urls);
+                Some((url, self.redactor.redact(&title)))
+            }
+            // no URL, which would show up as a domain
+            Decision::Private => Some((String::new(), PRIVATE_PLACEHOLDER.to_string())),
+            Decision::Drop => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(process: Option<&str>, title_pattern: Option<&str>, domain: Option<&str>, action: ExclusionAction) -> ExclusionRule {
+        ExclusionRule {
+            process: process.map(str::to_string),
+            title_pattern: title_pattern.map(str::to_string),
+            domain: domain.map(str::to_string),
+            action,
+        }
+    }
+
+    fn privacy(exclusions: Vec<ExclusionRule>) -> Privacy {
+        Privacy::from_config(&Config { exclusions, ..Config::default() })
+    }
+
+    #[test]
+    fn exclusion_kinds() {
+        use ExclusionAction::{Drop, Private};
+        // (rule, process, title, url, decision)
+        let cases = [
+            (rule(Some("KeePassXC.exe"), None, None, Drop), "keepassxc", "Passwords", None, Decision::Drop),
+            (rule(Some("keepassxc"), None, None, Drop), "KeePassXC.exe", "Passwords", None, Decision::Drop),
+            (rule(Some("keepassxc"), None, None, Drop), "keepass", "Passwords", None, Decision::Keep),
+            (rule(None, Some("(?i)private browsing"), None, Private), "firefox", "Bank - Private Browsing", None, Decision::Private),
+            (rule(None, Some("^Bank"), None, Private), "firefox", "My Bank", None, Decision::Keep),
+            (rule(None, None, Some("WWW.Bank.com"), Private), "chrome", "Accounts", Some("https://online.bank.com/a"), Decision::Private),
+            (rule(None, None, Some("bank.com"), Private), "chrome", "Accounts", Some("https://bank.com.evil.io/"), Decision::Keep),
+            (rule(None, None, Some("bank.com"), Private), "chrome", "Accounts", Some("https://notbank.com/"), Decision::Keep),
+            // a domain rule needs a URL
+            (rule(None, None, Some("bank.com"), Private), "chrome", "bank.com", None, Decision::Keep),
+            // every criterion that is set must match
+            (rule(Some("firefox"), Some("Private Browsing"), None, Drop), "firefox", "Bank - Private Browsing", None, Decision::Drop),
+            (rule(Some("firefox"), Some("Private Browsing"), None, Drop), "chrome", "Bank - Private Browsing", None, Decision::Keep),
+        ];
+        for (rule, process, title, url, expected) in cases {
+            let privacy = privacy(vec![rule.clone()]);
+            assert_eq!(privacy.window_decision(title, process, url), expected, "{:?} {} {}", rule, process, title);
+        }
+    }
+
+    #[test]
+    fn drop_wins_and_bad_rules_are_skipped() {
+        let privacy = privacy(vec![
+            rule(None, Some("("), None, ExclusionAction::Drop),
+            rule(None, None, None, ExclusionAction::Drop),
+            rule(Some("slack"), None, None, ExclusionAction::Private),
+            rule(None, Some("HR"), None, ExclusionAction::Drop),
+        ]);
+        assert_eq!(privacy.window_decision("general", "slack", None), Decision::Private);
+        assert_eq!(privacy.window_decision("HR - Slack", "slack", None), Decision::Drop);
+        assert_eq!(privacy.window_decision("anything", "code", None), Decision::Keep);
+    }
+
+    #[test]
+    fn recorded_windows() {
+        let privacy = privacy(Vec::new());
+        assert_eq!(
+            privacy.record_window(Decision::Keep, "Mail to me@example.com - Outlook", "olk.exe"),
+            Some(("Mail to [email] - Outlook".to_string(), "olk.exe".to_string()))
+        );
+        // private mode records the time as Decision::Private
+        let private = Some((PRIVATE_PLACEHOLDER.to_string(), PRIVATE_PLACEHOLDER.to_string()));
+        assert_eq!(privacy.record_window(Decision::Private, "Mail to me@example.com - Outlook", "olk.exe"), private);
+        assert_eq!(privacy.record_window(Decision::Drop, "Mail to me@example.com - Outlook", "olk.exe"), None);
+
+        let info = ProcessInfo {
+            pid: 42,
+            exe_path: Some("/usr/bin/git".to_string()),
+            command_line: Some("git commit --author me@example.com".to_string()),
+            file: Some("me@example.com notes.txt".to_string()),
+            ..ProcessInfo::default()
+        };
+        let kept = privacy.record_process(Decision::Keep, info.clone()).unwrap();
+        assert_eq!(kept.pid, 42);
+        assert_eq!(kept.exe_path.as_deref(), Some("/usr/bin/git"));
+        assert_eq!(kept.command_line.as_deref(), Some("git commit --author [email]"));
+        assert_eq!(kept.file.as_deref(), Some("[email] notes.txt"));
+        assert_eq!(privacy.record_process(Decision::Private, info.clone()), None);
+        assert_eq!(privacy.record_process(Decision::Drop, info), None);
+    }
+
+    #[test]
+    fn visits() {
+        let privacy = privacy(vec![
+            rule(None, None, Some("bank.com"), ExclusionAction::Private),
+            rule(None, None, Some("tracker.io"), ExclusionAction::Drop),
+            rule(Some("firefox"), Some("Private Browsing"), None, ExclusionAction::Drop),
+        ]);
+        assert_eq!(
+            privacy.visit("https://www.example.com/a?utm_source=x", "Write to me@example.com", None),
+            Some(("https://example.com/a".to_string(), "Write to [email]".to_string()))
+        );
+        // no URL, which would give the domain away
+        assert_eq!(privacy.visit("https://bank.com/accounts", "Accounts", None), Some((String::new(), PRIVATE_PLACEHOLDER.to_string())));
+        assert_eq!(privacy.visit("https://cdn.tracker.io/", "", None), None);
+        // process rules apply once the focused browser is known
+        assert_eq!(privacy.visit("https://example.com/", "Private Browsing", Some("firefox")), None);
+        assert!(privacy.visit("https://example.com/", "Private Browsing", None).is_some());
+    }
+}
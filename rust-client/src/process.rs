@@ -0,0 +1,694 @@
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use crate::config::Config;
+use crate::log_line;
+
+// -------------------- process details --------------------
+// A base name like "java" or "python.exe" says little about what is running,
+// so window entries also carry the executable path, command line and parent
+// process of the focused window, read when focus changes. App rules (see
+// AppRule) turn those into a friendlier app identity: which JetBrains IDE a
+// java process is, which app a bare electron binary runs.
+//
+// In the activity log the details follow the process name as JSON:
+//   Active window: 'Title' (proc: java) {"pid":4242,"exePath":"...",...}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(default, rename_all = "camelCase")]
+pub struct ProcessInfo {
+    pub pid: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub exe_path: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub command_line: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub parent_pid: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub parent_process: Option<String>,
+    // Derived by the app rules
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub app: Option<String>,
+    // The process couldn't be opened (elevated, protected or another user's),
+    // so there is no path and maybe no command line
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub inaccessible: bool,
+    // What runs in the terminal, and where (see add_terminal_job)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub foreground_command: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub working_directory: Option<String>,
+    // The git repository (directory name, top-level path) and branch being
+    // worked on (see git)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub repository: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub repository_path: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub branch: Option<String>,
+    // What an editor's title shows (see editors)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub project: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub file: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub language: Option<String>,
+}
+
+// The keys ProcessInfo adds to a window entry's data
+pub const DETAIL_KEYS: [&str; 15] = [
+    "pid",
+    "exePath",
+    "commandLine",
+    "parentPid",
+    "parentProcess",
+    "app",
+    "inaccessible",
+    "foregroundCommand",
+    "workingDirectory",
+    "repository",
+    "repositoryPath",
+    "branch",
+    "project",
+    "file",
+    "language",
+];
+
+// Quote arguments with spaces so the joined line reads like a shell command
+#[cfg(target_os = "linux")]
+fn join_args(args: &[u8]) -> String {
+    args.split(|b| *b == 0)
+        .filter(|arg| !arg.is_empty())
+        .map(|arg| {
+            let arg = String::from_utf8_lossy(arg);
+            if arg.contains(char::is_whitespace) {
+                format!("\"{}\"", arg)
+            } else {
+                arg.to_string()
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+// The /proc/<pid>/stat fields we use
+#[cfg(target_os = "linux")]
+#[derive(Clone)]
+struct Stat {
+    ppid: u32,
+    // controlling terminal, 0 for none
+    tty: i32,
+    // foreground process group of that terminal
+    tpgid: i32,
+    // in clock ticks since boot
+    start_time: u64,
+}
+
+#[cfg(target_os = "linux")]
+fn read_stat(pid: u32) -> Option<Stat> {
+    let stat = std::fs::read_to_string(format!("/proc/{}/stat", pid)).ok()?;
+    // the command name may contain spaces and parentheses, so count fields
+    // after the last ')': state, ppid, pgrp, session, tty_nr, tpgid, ...
+    let (_, rest) = stat.rsplit_once(')')?;
+    let fields: Vec<&str> = rest.split_whitespace().collect();
+    Some(Stat {
+        ppid: fields.get(1)?.parse().ok()?,
+        tty: fields.get(4)?.parse().ok()?,
+        tpgid: fields.get(5)?.parse().ok()?,
+        start_time: fields.get(19)?.parse().ok()?,
+    })
+}
+
+#[cfg(target_os = "linux")]
+fn command_line(pid: u32) -> Option<String> {
+    let args = std::fs::read(format!("/proc/{}/cmdline", pid)).ok()?;
+    Some(join_args(&args)).filter(|line| !line.is_empty())
+}
+
+#[cfg(target_os = "linux")]
+pub fn inspect(pid: u32) -> Option<ProcessInfo> {
+    let proc_dir = std::path::PathBuf::from(format!("/proc/{}", pid));
+    let parent_pid = Some(read_stat(pid)?.ppid).filter(|ppid| *ppid != 0);
+    let exe_path = std::fs::read_link(proc_dir.join("exe"))
+        .ok()
+        .map(|exe| exe.to_string_lossy().trim_end_matches(" (deleted)").to_string());
+    let command_line = command_line(pid);
+    Some(ProcessInfo {
+        pid,
+        // other users' executables can't be resolved
+        inaccessible: exe_path.is_none(),
+        exe_path,
+        command_line,
+        parent_pid,
+        parent_process: parent_pid.and_then(crate::process_name_from_pid),
+        ..Default::default()
+    })
+}
+
+#[cfg(windows)]
+pub fn inspect(pid: u32) -> Option<ProcessInfo> {
+    use windows::Win32::Foundation::CloseHandle;
+    use windows::Win32::System::Threading::{OpenProcess, PROCESS_QUERY_LIMITED_INFORMATION};
+
+    if pid == 0 {
+        return None;
+    }
+    unsafe {
+        let Ok(handle) = OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, false, pid) else {
+            // protected; the snapshot still knows its parent, and whether it's
+            // still running at all
+            let (_, parent_pid) = snapshot_entry(pid)?;
+            let parent_pid = Some(parent_pid).filter(|ppid| *ppid != 0);
+            return Some(ProcessInfo {
+                pid,
+                parent_pid,
+                parent_process: parent_pid.and_then(crate::process_name_from_pid),
+                inaccessible: true,
+                ..Default::default()
+            });
+        };
+        let parent_pid = windows_process::parent_pid(handle);
+        let info = ProcessInfo {
+            pid,
+            exe_path: windows_process::image_path(handle),
+            command_line: windows_process::command_line(handle),
+            parent_pid,
+            parent_process: parent_pid.and_then(crate::process_name_from_pid),
+            ..Default::default()
+        };
+        let _ = CloseHandle(handle);
+        Some(info)
+    }
+}
+
+#[cfg(all(unix, not(target_os = "linux")))]
+pub fn inspect(pid: u32) -> Option<ProcessInfo> {
+    Some(ProcessInfo { pid, ..Default::default() })
+}
+
+// -------------------- terminal jobs --------------------
+// A terminal's title rarely says what runs inside it. Each tab's shell has a
+// terminal device whose foreground process group (tpgid in /proc/<pid>/stat)
+// is what the user is running there, e.g. "cargo build", and its cwd says
+// where. With several tabs open the one whose command shows up in the title
+// wins, else the most recently started.
+
+// Terminal emulators by process name; categories use the list too
+pub const TERMINALS: &[&str] = &[
+    "alacritty",
+    "foot",
+    "ghostty",
+    "gnome-terminal-server",
+    "iterm2",
+    "kgx",
+    "kitty",
+    "konsole",
+    "lxterminal",
+    "mate-terminal",
+    "ptyxis-agent",
+    "qterminal",
+    "st",
+    "terminal",
+    "terminator",
+    "terminology",
+    "tilix",
+    "urxvt",
+    "wezterm-gui",
+    "windowsterminal",
+    "xfce4-terminal",
+    "xterm",
+];
+
+// The children of all of `pid`'s threads (a multi-threaded emulator may
+// start shells from any of them); None when the kernel was built without
+// CONFIG_PROC_CHILDREN
+#[cfg(target_os = "linux")]
+fn children(pid: u32) -> Option<Vec<u32>> {
+    // gone already: no children
+    let Ok(tasks) = std::fs::read_dir(format!("/proc/{}/task", pid)) else {
+        return Some(Vec::new());
+    };
+    let lists: Vec<String> = tasks
+        .flatten()
+        .filter_map(|task| std::fs::read_to_string(task.path().join("children")).ok())
+        .collect();
+    if lists.is_empty() {
+        return None;
+    }
+    Some(lists.iter().flat_map(|list| list.split_whitespace().filter_map(|child| child.parse().ok())).collect())
+}
+
+// The processes below `pid`, walking down from it rather than reading every
+// process in /proc, which is only the fallback
+#[cfg(target_os = "linux")]
+fn descendants(pid: u32) -> Vec<(u32, Stat)> {
+    let mut all: Option<Vec<(u32, Stat)>> = None;
+    let mut found = Vec::new();
+    let mut parents = vec![pid];
+    while let Some(parent) = parents.pop() {
+        let below: Vec<(u32, Stat)> = match children(parent) {
+            Some(pids) => pids.into_iter().filter_map(|pid| Some((pid, read_stat(pid)?))).collect(),
+            None => {
+                let all = all.get_or_insert_with(|| {
+                    std::fs::read_dir("/proc")
+                        .into_iter()
+                        .flatten()
+                        .flatten()
+                        .filter_map(|entry| entry.file_name().to_str()?.parse().ok())
+                        .filter_map(|pid| Some((pid, read_stat(pid)?)))
+                        .collect()
+                });
+                all.iter().filter(|(_, stat)| stat.ppid == parent).cloned().collect()
+            }
+        };
+        parents.extend(below.iter().map(|(pid, _)| *pid));
+        found.extend(below);
+    }
+    found
+}
+
+// Fill in the foreground command and its working directory when `info` is a
+// terminal emulator
+#[cfg(target_os = "linux")]
+pub fn add_terminal_job(mut info: ProcessInfo, title: &str) -> ProcessInfo {
+    let name = info.exe_path.as_deref().and_then(|path| std::path::Path::new(path).file_name()).map(|name| name.to_string_lossy().to_string());
+    if !name.is_some_and(|name| TERMINALS.contains(&name.as_str())) {
+        return info;
+    }
+
+    // one foreground group per terminal device below the emulator
+    let stats: std::collections::HashMap<u32, Stat> = descendants(info.pid).into_iter().collect();
+    let groups: std::collections::BTreeSet<u32> =
+        stats.values().filter(|stat| stat.tty != 0 && stat.tpgid > 0).map(|stat| stat.tpgid as u32).collect();
+
+    // (leader pid, command line, start time); a group whose leader has
+    // exited (the head of a finished pipeline) is skipped
+    let jobs: Vec<(u32, String, u64)> = groups
+        .into_iter()
+        .filter_map(|leader| Some((leader, command_line(leader)?, stats.get(&leader)?.start_time)))
+        .collect();
+    let title = title.to_lowercase();
+    let in_title = |command: &str| {
+        let program = command.split_whitespace().next().unwrap_or("");
+        let program = program.rsplit('/').next().unwrap_or(program).trim_start_matches('-').to_lowercase();
+        !program.is_empty() && title.contains(&program)
+    };
+    let job = jobs
+        .iter()
+        .filter(|(_, command, _)| in_title(command))
+        .max_by_key(|(_, _, start)| *start)
+        .or_else(|| jobs.iter().max_by_key(|(_, _, start)| *start));
+    if let Some((leader, command, _)) = job {
+        info.foreground_command = Some(command.clone());
+        info.working_directory = std::fs::read_link(format!("/proc/{}/cwd", leader)).ok().map(|cwd| cwd.to_string_lossy().to_string());
+    }
+    info
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn add_terminal_job(info: ProcessInfo, _title: &str) -> ProcessInfo {
+    info
+}
+
+// -------------------- naming inaccessible processes --------------------
+// Elevated processes can still be opened for limited information, protected
+// ones (anti-cheat, some system services) not at all. A process snapshot
+// names every process, so those are looked up in the last one, taken again
+// when it is too old or misses the pid. The window loop falls back to the
+// window class, then to INACCESSIBLE, when even that fails.
+
+// Process name for a window whose process is known but couldn't be named
+pub const INACCESSIBLE: &str = "inaccessible";
+
+#[cfg(windows)]
+const SNAPSHOT_MAX_AGE: std::time::Duration = std::time::Duration::from_secs(30);
+// A pid that has exited misses every time; don't snapshot for it on every event
+#[cfg(windows)]
+const SNAPSHOT_MIN_AGE: std::time::Duration = std::time::Duration::from_secs(1);
+
+// pid -> (executable name, parent pid), and when the snapshot was taken
+#[cfg(windows)]
+type Snapshot = (std::time::Instant, std::collections::HashMap<u32, (String, u32)>);
+
+#[cfg(windows)]
+static SNAPSHOT: std::sync::Mutex<Option<Snapshot>> = std::sync::Mutex::new(None);
+
+#[cfg(windows)]
+fn snapshot_entry(pid: u32) -> Option<(String, u32)> {
+    let mut snapshot = SNAPSHOT.lock().unwrap();
+    let stale = match snapshot.as_ref() {
+        Some((taken, processes)) => {
+            let age = taken.elapsed();
+            age >= SNAPSHOT_MAX_AGE || (age >= SNAPSHOT_MIN_AGE && !processes.contains_key(&pid))
+        }
+        None => true,
+    };
+    if stale {
+        *snapshot = Some((std::time::Instant::now(), unsafe { windows_process::processes() }));
+    }
+    snapshot.as_ref()?.1.get(&pid).cloned()
+}
+
+// Full path from the limited-information query, which works for elevated
+// processes too
+#[cfg(windows)]
+pub fn image_path(pid: u32) -> Option<String> {
+    use windows::Win32::Foundation::CloseHandle;
+    use windows::Win32::System::Threading::{OpenProcess, PROCESS_QUERY_LIMITED_INFORMATION};
+
+    unsafe {
+        let handle = OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, false, pid).ok()?;
+        let path = windows_process::image_path(handle);
+        let _ = CloseHandle(handle);
+        path
+    }
+}
+
+#[cfg(windows)]
+pub fn snapshot_name(pid: u32) -> Option<String> {
+    snapshot_entry(pid).map(|(name, _)| name)
+}
+
+// PROCESS_QUERY_LIMITED_INFORMATION is all these need, which is granted for
+// more processes than the full query
+#[cfg(windows)]
+mod windows_process {
+    use std::collections::HashMap;
+    use std::ffi::c_void;
+    use windows::core::PWSTR;
+    use windows::Wdk::System::Threading::{NtQueryInformationProcess, ProcessBasicInformation, ProcessCommandLineInformation};
+    use windows::Win32::Foundation::{CloseHandle, HANDLE, UNICODE_STRING};
+    use windows::Win32::System::Diagnostics::ToolHelp::{
+        CreateToolhelp32Snapshot, Process32FirstW, Process32NextW, PROCESSENTRY32W, TH32CS_SNAPPROCESS,
+    };
+    use windows::Win32::System::Threading::{QueryFullProcessImageNameW, PROCESS_BASIC_INFORMATION, PROCESS_NAME_WIN32};
+
+    pub unsafe fn image_path(handle: HANDLE) -> Option<String> {
+        let mut buf = [0u16; 1024];
+        let mut len = buf.len() as u32;
+        QueryFullProcessImageNameW(handle, PROCESS_NAME_WIN32, PWSTR(buf.as_mut_ptr()), &mut len).ok()?;
+        Some(String::from_utf16_lossy(&buf[..len as usize]))
+    }
+
+    pub unsafe fn parent_pid(handle: HANDLE) -> Option<u32> {
+        let mut info = PROCESS_BASIC_INFORMATION::default();
+        let mut len = 0u32;
+        let size = std::mem::size_of::<PROCESS_BASIC_INFORMATION>() as u32;
+        NtQueryInformationProcess(handle, ProcessBasicInformation, &mut info as *mut _ as *mut c_void, size, &mut len).ok().ok()?;
+        u32::try_from(info.InheritedFromUniqueProcessId).ok().filter(|ppid| *ppid != 0)
+    }
+
+    // Windows 8.1+ hands out the command line directly: a UNICODE_STRING
+    // whose buffer follows it in the output
+    pub unsafe fn command_line(handle: HANDLE) -> Option<String> {
+        let mut len = 0u32;
+        let _ = NtQueryInformationProcess(handle, ProcessCommandLineInformation, std::ptr::null_mut(), 0, &mut len);
+        if (len as usize) < std::mem::size_of::<UNICODE_STRING>() {
+            return None;
+        }
+        // u64s keep the UNICODE_STRING aligned
+        let mut buf = vec![0u64; (len as usize).div_ceil(8)];
+        NtQueryInformationProcess(handle, ProcessCommandLineInformation, buf.as_mut_ptr() as *mut c_void, len, &mut len).ok().ok()?;
+        let text = &*(buf.as_ptr() as *const UNICODE_STRING);
+        if text.Buffer.is_null() || text.Length == 0 {
+            return None;
+        }
+        let chars = std::slice::from_raw_parts(text.Buffer.0, text.Length as usize / 2);
+        Some(String::from_utf16_lossy(chars))
+    }
+
+    // pid -> (executable name, parent pid) for every running process
+    pub unsafe fn processes() -> HashMap<u32, (String, u32)> {
+        let mut processes = HashMap::new();
+        let Ok(snapshot) = CreateToolhelp32Snapshot(TH32CS_SNAPPROCESS, 0) else {
+            return processes;
+        };
+        let mut entry = PROCESSENTRY32W { dwSize: std::mem::size_of::<PROCESSENTRY32W>() as u32, ..Default::default() };
+        let mut more = Process32FirstW(snapshot, &mut entry).is_ok();
+        while more {
+            let len = entry.szExeFile.iter().position(|c| *c == 0).unwrap_or(entry.szExeFile.len());
+            let name = String::from_utf16_lossy(&entry.szExeFile[..len]);
+            processes.insert(entry.th32ProcessID, (name, entry.th32ParentProcessID));
+            more = Process32NextW(snapshot, &mut entry).is_ok();
+        }
+        let _ = CloseHandle(snapshot);
+        processes
+    }
+}
+
+// " {json}" to append to an "Active window" line; empty without details
+pub fn format_details(info: Option<&ProcessInfo>) -> String {
+    info.and_then(|info| serde_json::to_string(info).ok()).map(|json| format!(" {}", json)).unwrap_or_default()
+}
+
+// Split the details off an "Active window" line. Quotes inside JSON strings
+// are escaped, so the last ` {"pid":` can only be where the details start,
+// whatever the title or command line contains.
+pub fn split_details(content: &str) -> (&str, Option<Map<String, Value>>) {
+    if let Some(start) = content.rfind(" {\"pid\":") {
+        if let Ok(Value::Object(details)) = serde_json::from_str(&content[start + 1..]) {
+            return (&content[..start], Some(details));
+        }
+    }
+    (content, None)
+}
+
+// -------------------- app identity --------------------
+// Rules from the "appRules" config setting run first, then the built-in ones;
+// the first match names the app. Every criterion that is set must match, e.g.
+//   { "process": "java", "commandLinePattern": "-jar (\\w+)\\.jar", "app": "$1" }
+// "$1" and friends in `app` are filled in from the command line pattern, or
+// the path pattern when only that has groups.
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(default, rename_all = "camelCase")]
+pub struct AppRule {
+    // Process name, case-insensitive, ".exe" optional
+    pub process: Option<String>,
+    // Regexes matched against the executable path and the command line
+    pub path_pattern: Option<String>,
+    pub command_line_pattern: Option<String>,
+    pub app: String,
+}
+
+// JetBrains launchers (idea64.exe, bin/pycharm) and the paths selector that
+// java-launched IDEs pass (-Didea.paths.selector=PyCharmCE2024.1)
+pub const JETBRAINS_PRODUCTS: &[(&str, &str)] = &[
+    ("idea", "IntelliJ IDEA"),
+    ("pycharm", "PyCharm"),
+    ("clion", "CLion"),
+    ("goland", "GoLand"),
+    ("webstorm", "WebStorm"),
+    ("phpstorm", "PhpStorm"),
+    ("rubymine", "RubyMine"),
+    ("rider", "Rider"),
+    ("datagrip", "DataGrip"),
+    ("dataspell", "DataSpell"),
+    ("rustrover", "RustRover"),
+    ("studio", "Android Studio"),
+];
+
+// The names a JetBrains IDE's windows go by: the launcher as process name,
+// the product as app identity (for java-launched ones)
+pub fn jetbrains_names() -> Vec<String> {
+    JETBRAINS_PRODUCTS.iter().flat_map(|(launcher, product)| [launcher.to_string(), product.to_lowercase()]).collect()
+}
+
+// How rules name processes: "Code.exe" -> "code"
+pub fn process_name(name: &str) -> String {
+    let name = name.trim().to_lowercase();
+    name.strip_suffix(".exe").map(str::to_string).unwrap_or(name)
+}
+
+fn built_in_rules() -> Vec<AppRule> {
+    let mut rules = Vec::new();
+    for (launcher, product) in JETBRAINS_PRODUCTS {
+        rules.push(AppRule {
+            path_pattern: Some(format!(r"(?i)[/\\]{}(?:64)?(?:\.exe|\.sh)?$", launcher)),
+            app: product.to_string(),
+            ..Default::default()
+        });
+        rules.push(AppRule {
+            process: Some("java".to_string()),
+            command_line_pattern: Some(format!(r"(?i)-Didea\.paths\.selector=(?:IntelliJ|Android)?{}", launcher)),
+            app: product.to_string(),
+            ..Default::default()
+        });
+    }
+    // A shared electron binary runs the app named by its resources, e.g.
+    // "electron30 /usr/lib/obsidian/app.asar"
+    rules.push(AppRule {
+        path_pattern: Some(r"(?i)[/\\]electron\d*(?:\.exe)?$".to_string()),
+        command_line_pattern: Some(r#"([^/\\\s"]+)[/\\](?:resources[/\\])?app(?:\.asar)?(?:[\s"/\\]|$)"#.to_string()),
+        app: "$1".to_string(),
+        ..Default::default()
+    });
+    rules
+}
+
+struct CompiledAppRule {
+    process: Option<String>,
+    path: Option<Regex>,
+    command_line: Option<Regex>,
+    app: String,
+}
+
+fn compile_pattern(pattern: &Option<String>) -> Result<Option<Regex>, ()> {
+    match pattern {
+        Some(pattern) => Regex::new(pattern).map(Some).map_err(|e| {
+            log_line(&format!("Ignoring app rule with invalid pattern '{}': {}", pattern, e));
+        }),
+        None => Ok(None),
+    }
+}
+
+impl CompiledAppRule {
+    fn compile(rule: &AppRule) -> Option<CompiledAppRule> {
+        let path = compile_pattern(&rule.path_pattern).ok()?;
+        let command_line = compile_pattern(&rule.command_line_pattern).ok()?;
+        if rule.app.trim().is_empty() || (rule.process.is_none() && path.is_none() && command_line.is_none()) {
+            return None;
+        }
+        Some(CompiledAppRule {
+            process: rule.process.as_deref().map(process_name),
+            path,
+            command_line,
+            app: rule.app.clone(),
+        })
+    }
+
+    fn identify(&self, process: &str, info: &ProcessInfo) -> Option<String> {
+        if self.process.as_ref().is_some_and(|wanted| *wanted != process_name(process)) {
+            return None;
+        }
+        let mut app = self.app.clone();
+        for (re, text) in [(&self.path, &info.exe_path), (&self.command_line, &info.command_line)] {
+            let Some(re) = re else { continue };
+            let captures = re.captures(text.as_deref()?)?;
+            if captures.len() > 1 {
+                let mut expanded = String::new();
+                captures.expand(&self.app, &mut expanded);
+                app = expanded;
+            }
+        }
+        Some(app).filter(|app| !app.trim().is_empty())
+    }
+}
+
+pub struct AppRules {
+    rules: Vec<CompiledAppRule>,
+}
+
+impl AppRules {
+    pub fn from_config(config: &Config) -> AppRules {
+        let rules = config.app_rules.iter().chain(built_in_rules().iter()).filter_map(CompiledAppRule::compile).collect();
+        AppRules { rules }
+    }
+
+    // Fill in `info.app` from the first matching rule
+    pub fn identify(&self, process: &str, mut info: ProcessInfo) -> ProcessInfo {
+        info.app = self.rules.iter().find_map(|rule| rule.identify(process, &info));
+        info
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn details_round_trip() {
+        let info = ProcessInfo {
+            pid: 4242,
+            exe_path: Some(r#"C:\Program Files\My "App"\app.exe"#.to_string()),
+            command_line: Some(r#"app.exe --data {"pid":1} 'x'"#.to_string()),
+            parent_pid: Some(1),
+            working_directory: Some("/home/me/src/chronos".to_string()),
+            ..ProcessInfo::default()
+        };
+        // a title that looks like the start of the details
+        let title = r#"notes {"pid":2} "quoted" - Editor"#;
+        let content = format!("Active window: '{}' (proc: app.exe){}", title, format_details(Some(&info)));
+        let (window, details) = split_details(&content);
+        assert_eq!(window, format!("Active window: '{}' (proc: app.exe)", title));
+        let parsed: ProcessInfo = serde_json::from_value(Value::Object(details.unwrap())).unwrap();
+        assert_eq!(parsed, info);
+
+        // without details nothing is split off, even when the title has a ` {"pid":`
+        let content = format!("Active window: '{}' (proc: app.exe){}", title, format_details(None));
+        assert_eq!(split_details(&content), (content.as_str(), None));
+        let content = r#"Active window: 'x {"pid":3}' (proc: app.exe)"#;
+        assert_eq!(split_details(content), (content, None));
+    }
+
+    fn identify(rules: &AppRules, process: &str, exe_path: &str, command_line: &str) -> Option<String> {
+        let info = ProcessInfo {
+            exe_path: Some(exe_path.to_string()).filter(|path| !path.is_empty()),
+            command_line: Some(command_line.to_string()).filter(|line| !line.is_empty()),
+            ..ProcessInfo::default()
+        };
+        rules.identify(process, info).app
+    }
+
+    #[test]
+    fn built_in_apps() {
+        let rules = AppRules::from_config(&Config::default());
+        let cases = [
+            ("idea64.exe", r"C:\Program Files\JetBrains\IntelliJ IDEA 2024.1\bin\idea64.exe", "", Some("IntelliJ IDEA")),
+            ("pycharm.sh", "/opt/pycharm/bin/pycharm.sh", "", Some("PyCharm")),
+            ("rustrover", "/home/me/.local/share/JetBrains/Toolbox/apps/rustrover/bin/rustrover", "", Some("RustRover")),
+            ("java", "/usr/lib/jvm/java-17/bin/java", "java -Xmx2g -Didea.paths.selector=PyCharmCE2024.1 com.intellij.idea.Main", Some("PyCharm")),
+            ("java", "/usr/bin/java", "java -Didea.paths.selector=AndroidStudio2023.3 -cp lib/*", Some("Android Studio")),
+            ("java", "/usr/bin/java", "java -Didea.paths.selector=IdeaIC2024.1", Some("IntelliJ IDEA")),
+            // other java programs are just java
+            ("java", "/usr/bin/java", "java -jar minecraft.jar", None),
+            // a shared electron runs the app named by its resources
+            ("electron", "/usr/lib/electron30/electron", "/usr/lib/electron30/electron /usr/lib/obsidian/app.asar", Some("obsidian")),
+            ("electron.exe", r"C:\tools\electron.exe", r#""C:\tools\electron.exe" "C:\Apps\Notes\resources\app.asar""#, Some("Notes")),
+            ("electron", "/usr/lib/electron30/electron", "/usr/lib/electron30/electron --version", None),
+            ("code", "/usr/share/code/code", "/usr/share/code/code .", None),
+        ];
+        for (process, exe_path, command_line, expected) in cases {
+            assert_eq!(identify(&rules, process, exe_path, command_line).as_deref(), expected, "{} {}", process, command_line);
+        }
+    }
+
+    #[test]
+    fn user_rules_come_first() {
+        let config = Config {
+            app_rules: vec![
+                AppRule {
+                    process: Some("Java.exe".to_string()),
+                    command_line_pattern: Some(r"-jar (\w+)\.jar".to_string()),
+                    app: "$1".to_string(),
+                    ..Default::default()
+                },
+                AppRule { path_pattern: Some(r"(?i)[/\\]pycharm\.sh$".to_string()), app: "PyCharm Work".to_string(), ..Default::default() },
+                // invalid, ignored
+                AppRule {
+                    process: Some("code".to_string()),
+                    command_line_pattern: Some("(".to_string()),
+                    app: "Broken".to_string(),
+                    ..Default::default()
+                },
+                // names nothing to match on, ignored
+                AppRule { app: "Everything".to_string(), ..Default::default() },
+            ],
+            ..Config::default()
+        };
+        let rules = AppRules::from_config(&config);
+        assert_eq!(identify(&rules, "java", "/usr/bin/java", "java -jar minecraft.jar").as_deref(), Some("minecraft"));
+        assert_eq!(identify(&rules, "pycharm.sh", "/opt/pycharm/bin/pycharm.sh", "").as_deref(), Some("PyCharm Work"));
+        assert_eq!(identify(&rules, "clion", "/opt/clion/bin/clion", "").as_deref(), Some("CLion"));
+        assert_eq!(identify(&rules, "code", "/usr/share/code/code", "code .").as_deref(), None);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn joined_args() {
+        assert_eq!(join_args(b"git\0commit\0-m\0fix the bug\0"), r#"git commit -m "fix the bug""#);
+        assert_eq!(join_args(b"vim\0\0notes.txt\0"), "vim notes.txt");
+        assert_eq!(join_args(b""), "");
+    }
+}
@@ -0,0 +1,153 @@
+use chrono::NaiveDateTime;
+use std::collections::HashMap;
+
+use crate::browser_time;
+use crate::entries::{entry_time, window_sessions};
+use crate::LogEntry;
+
+// -------------------- local reports --------------------
+// Time per app and per website between two local times, straight from the
+// activity log, for `chronos report`. Entries tagged with issues (see
+// issues) also count towards each issue they mention, and categorised ones
+// (see categories) towards their category and the productivity score.
+
+pub struct Report {
+    // (name, seconds), longest first
+    pub apps: Vec<(String, i64)>,
+    pub domains: Vec<(String, i64)>,
+    // ("repository (branch)", seconds), see git
+    pub repositories: Vec<(String, i64)>,
+    // (issue, seconds); an entry naming two issues counts for both
+    pub issues: Vec<(String, i64)>,
+    // ("Work > Coding", seconds) for each category and its parents, each
+    // followed by its subcategories, longest first
+    pub categories: Vec<(String, i64)>,
+    // Average score of the categorised time, -2 to 2
+    pub productivity: Option<f64>,
+}
+
+impl Report {
+    pub fn total_seconds(&self) -> i64 {
+        self.apps.iter().map(|(_, seconds)| seconds).sum()
+    }
+}
+
+fn issues(entry: &LogEntry) -> impl Iterator<Item = &str> {
+    entry.data.get("issues").and_then(|v| v.as_array()).into_iter().flatten().filter_map(|v| v.as_str())
+}
+
+const UNCATEGORIZED: &str = "Uncategorized";
+
+fn outline(totals: HashMap<String, i64>) -> Vec<(String, i64)> {
+    let mut all: HashMap<String, i64> = HashMap::new();
+    for (name, seconds) in totals {
+        let parts: Vec<&str> = name.split(" > ").collect();
+        for depth in 1..=parts.len() {
+            *all.entry(parts[..depth].join(" > ")).or_insert(0) += seconds;
+        }
+    }
+    fn add(all: &[(String, i64)], parent: Option<&str>, out: &mut Vec<(String, i64)>) {
+        for (name, seconds) in all.iter().filter(|(name, _)| name.rsplit_once(" > ").map(|(up, _)| up) == parent) {
+            out.push((name.clone(), *seconds));
+            add(all, Some(name), out);
+        }
+    }
+    let mut out = Vec::new();
+    add(&sorted(all), None, &mut out);
+    out
+}
+
+fn sorted(totals: HashMap<String, i64>) -> Vec<(String, i64)> {
+    let mut totals: Vec<(String, i64)> = totals.into_iter().filter(|(_, seconds)| *seconds > 0).collect();
+    totals.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    totals
+}
+
+pub fn build(mut entries: Vec<LogEntry>, from: NaiveDateTime, to: NaiveDateTime) -> Report {
+    // Window sessions are clipped to the range
+    let mut apps: HashMap<String, i64> = HashMap::new();
+    let mut repositories: HashMap<String, i64> = HashMap::new();
+    let mut issue_time: HashMap<String, i64> = HashMap::new();
+    let mut category_time: HashMap<String, i64> = HashMap::new();
+    // (score x seconds, seconds) over the scored time
+    let mut scored = (0.0, 0.0);
+    for (i, start, end) in window_sessions(&entries) {
+        let (start, end) = (start.max(from), end.min(to));
+        if end > start {
+            // the app identity when the app rules found one (see process)
+            let app = ["app", "processName"]
+                .iter()
+                .find_map(|key| entries[i].data.get(*key).and_then(|v| v.as_str()))
+                .unwrap_or("Unknown");
+            *apps.entry(app.to_string()).or_insert(0) += (end - start).num_milliseconds();
+            let field = |key: &str| entries[i].data.get(key).and_then(|v| v.as_str());
+            if let Some(repository) = field("repository") {
+                let name = match field("branch") {
+                    Some(branch) => format!("{} ({})", repository, branch),
+                    None => repository.to_string(),
+                };
+                *repositories.entry(name).or_insert(0) += (end - start).num_milliseconds();
+            }
+            // browser windows count through their visits below
+            if field("processName").and_then(browser_time::browser_family).is_none() {
+                for issue in issues(&entries[i]) {
+                    *issue_time.entry(issue.to_string()).or_insert(0) += (end - start).num_milliseconds();
+                }
+                let category = field("category").unwrap_or(UNCATEGORIZED);
+                *category_time.entry(category.to_string()).or_insert(0) += (end - start).num_milliseconds();
+                if let Some(score) = entries[i].data.get("productivity").and_then(|v| v.as_i64()) {
+                    let seconds = (end - start).num_milliseconds() as f64 / 1000.0;
+                    scored = (scored.0 + score as f64 * seconds, scored.1 + seconds);
+                }
+            }
+        }
+    }
+    let to_seconds = |totals: HashMap<String, i64>| totals.into_iter().map(|(name, millis)| (name, (millis + 500) / 1000)).collect();
+    let apps = to_seconds(apps);
+    let repositories = to_seconds(repositories);
+    let mut issue_time: HashMap<String, i64> = to_seconds(issue_time);
+    let mut category_time: HashMap<String, i64> = to_seconds(category_time);
+
+    // Browser time is attributed per visit; count the visits made in range
+    browser_time::annotate_browser_entries(&mut entries);
+    let mut domains: HashMap<String, i64> = HashMap::new();
+    for entry in entries.iter().filter(|e| e.log_type == "browser") {
+        let in_range = entry_time(entry).is_some_and(|t| t >= from && t < to);
+        let domain = entry.data.get("domain").and_then(|v| v.as_str());
+        let seconds = entry.data.get("focusSeconds").and_then(|v| v.as_i64()).unwrap_or(0);
+        if let (true, Some(domain)) = (in_range, domain) {
+            *domains.entry(domain.to_string()).or_insert(0) += seconds;
+        }
+        if in_range {
+            for issue in issues(entry) {
+                *issue_time.entry(issue.to_string()).or_insert(0) += seconds;
+            }
+            let category = entry.data.get("category").and_then(|v| v.as_str()).unwrap_or(UNCATEGORIZED);
+            *category_time.entry(category.to_string()).or_insert(0) += seconds;
+            if let Some(score) = entry.data.get("productivity").and_then(|v| v.as_i64()) {
+                scored = (scored.0 + (score * seconds) as f64, scored.1 + seconds as f64);
+            }
+        }
+    }
+
+    Report {
+        apps: sorted(apps),
+        domains: sorted(domains),
+        repositories: sorted(repositories),
+        issues: sorted(issue_time),
+        categories: outline(category_time),
+        productivity: Some(scored.0 / scored.1).filter(|_| scored.1 >= 1.0),
+    }
+}
+
+// "2h 05m", "12m", "40s"
+pub fn format_duration(seconds: i64) -> String {
+    let (hours, minutes) = (seconds / 3600, seconds % 3600 / 60);
+    if hours > 0 {
+        format!("{}h {:02}m", hours, minutes)
+    } else if minutes > 0 {
+        format!("{}m", minutes)
+    } else {
+        format!("{}s", seconds)
+    }
+}
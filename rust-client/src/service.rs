@@ -0,0 +1,270 @@
+// -------------------- running on login --------------------
+// On Windows the installer starts Chronos on login (see installers/). On
+// Linux `chronos service install` does: a systemd user unit when the session
+// is managed by systemd, an XDG autostart entry otherwise. Under systemd the
+// tracker reports readiness and keeps the watchdog fed (see notify), so a
+// hung loop gets restarted rather than silently recording nothing.
+
+// Tell systemd how we're doing ("READY=1", "WATCHDOG=1", "STOPPING=1");
+// does nothing unless started by a Type=notify unit
+#[cfg(target_os = "linux")]
+pub fn notify(state: &str) {
+    use std::os::linux::net::SocketAddrExt;
+    use std::os::unix::net::{SocketAddr, UnixDatagram};
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    // the watchdog is fed on every loop, so a bad NOTIFY_SOCKET is logged once
+    static FAILED: AtomicBool = AtomicBool::new(false);
+
+    let Some(path) = std::env::var_os("NOTIFY_SOCKET") else {
+        return;
+    };
+    let result = UnixDatagram::unbound().and_then(|socket| match path.as_encoded_bytes().strip_prefix(b"@") {
+        // abstract socket
+        Some(name) => socket.send_to_addr(state.as_bytes(), &SocketAddr::from_abstract_name(name)?),
+        None => socket.send_to(state.as_bytes(), &path),
+    });
+    if let Err(e) = result {
+        if !FAILED.swap(true, Ordering::Relaxed) {
+            crate::log_line(&format!("Could not notify systemd ({}): {}", state, e));
+        }
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn notify(_state: &str) {}
+
+#[cfg(target_os = "linux")]
+pub use linux::{install, status, uninstall};
+
+#[cfg(not(target_os = "linux"))]
+fn unsupported() -> Result<(), Box<dyn std::error::Error>> {
+    Err("`chronos service` is for Linux; on Windows the installer sets up starting on login".into())
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn install(_autostart: bool) -> Result<(), Box<dyn std::error::Error>> {
+    unsupported()
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn uninstall() -> Result<(), Box<dyn std::error::Error>> {
+    unsupported()
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn status() -> Result<(), Box<dyn std::error::Error>> {
+    unsupported()
+}
+
+#[cfg(target_os = "linux")]
+mod linux {
+    use std::error::Error;
+    use std::path::{Path, PathBuf};
+    use std::process::Command;
+
+    use crate::instance;
+
+    const UNIT_NAME: &str = "chronos.service";
+
+    fn config_dir() -> PathBuf {
+        std::env::var_os("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .filter(|dir| dir.is_absolute())
+            .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))
+            .unwrap_or_else(|| PathBuf::from(".config"))
+    }
+
+    fn unit_path() -> PathBuf {
+        config_dir().join("systemd/user").join(UNIT_NAME)
+    }
+
+    fn desktop_path() -> PathBuf {
+        config_dir().join("autostart/chronos.desktop")
+    }
+
+    // `systemctl --user <args>`, None when systemctl can't be run at all
+    fn systemctl(args: &[&str]) -> Option<(bool, String)> {
+        let output = Command::new("systemctl").arg("--user").args(args).output().ok()?;
+        let text = if output.status.success() { output.stdout } else { output.stderr };
+        Some((output.status.success(), String::from_utf8_lossy(&text).trim().to_string()))
+    }
+
+    // A systemd user manager that knows about the graphical session; without
+    // graphical-session.target the unit would never start
+    fn systemd_session() -> bool {
+        matches!(systemctl(&["is-active", "graphical-session.target"]), Some((true, _)))
+    }
+
+    fn exe() -> Result<String, Box<dyn Error>> {
+        Ok(std::env::current_exe()?.display().to_string())
+    }
+
+    // ExecStart= takes C-style escapes inside quotes; % starts a specifier
+    // and $ a variable
+    fn systemd_quote(path: &str) -> String {
+        format!("\"{}\"", path.replace('\\', "\\\\").replace('"', "\\\"").replace('%', "%%").replace('$', "$$"))
+    }
+
+    // Exec= in a desktop entry escapes ", `, $ and \ inside quotes with a
+    // backslash, and the value is unescaped as a string before that, which
+    // doubles every backslash again; % starts a field code
+    fn desktop_quote(path: &str) -> String {
+        let quoted = path.replace('\\', "\\\\").replace('"', "\\\"").replace('`', "\\`").replace('$', "\\$");
+        format!("\"{}\"", quoted.replace('\\', "\\\\").replace('%', "%%"))
+    }
+
+    fn unit(exe: &str) -> String {
+        format!(
+            "[Unit]
+Description=Chronos activity tracker
+After=graphical-session.target
+PartOf=graphical-session.target
+
+[Service]
+Type=notify
+NotifyAccess=main
+ExecStart={} run
+Restart=on-failure
+RestartSec=10
+# the tracker pings at least every 10 seconds
+WatchdogSec=60
+
+[Install]
+WantedBy=graphical-session.target
+",
+            systemd_quote(exe)
+        )
+    }
+
+    fn desktop_entry(exe: &str) -> String {
+        format!(
+            "[Desktop Entry]
+Type=Application
+Name=Chronos
+Comment=Tracks the apps and websites you use
+Exec={} run
+Terminal=false
+NoDisplay=true
+X-GNOME-Autostart-enabled=true
+",
+            desktop_quote(exe)
+        )
+    }
+
+    fn write(path: &Path, content: &str) -> Result<(), Box<dyn Error>> {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(path, content).map_err(|e| format!("could not write {}: {}", path.display(), e).into())
+    }
+
+    pub fn install(autostart: bool) -> Result<(), Box<dyn Error>> {
+        let exe = exe()?;
+        if autostart || !systemd_session() {
+            let path = desktop_path();
+            write(&path, &desktop_entry(&exe))?;
+            println!("✅ Chronos will start when you log in ({}).", path.display());
+            if !autostart {
+                println!("No systemd graphical session was found, so this uses XDG autostart.");
+            }
+            return Ok(());
+        }
+
+        let path = unit_path();
+        write(&path, &unit(&exe))?;
+        // an older autostart entry would start a second copy
+        let _ = std::fs::remove_file(desktop_path());
+        systemctl(&["daemon-reload"]);
+        match instance::running_instance() {
+            Some(owner) => {
+                if let Some((false, error)) = systemctl(&["enable", UNIT_NAME]) {
+                    return Err(format!("systemctl --user enable {} failed: {}", UNIT_NAME, error).into());
+                }
+                println!("✅ Installed {}; it starts on your next login.", path.display());
+                println!(
+                    "Chronos is already running (pid {}). Stop it and run `systemctl --user start {}` to switch now.",
+                    owner.pid, UNIT_NAME
+                );
+            }
+            None => {
+                if let Some((false, error)) = systemctl(&["enable", "--now", UNIT_NAME]) {
+                    return Err(format!("systemctl --user enable --now {} failed: {}", UNIT_NAME, error).into());
+                }
+                println!("✅ Installed and started {}.", path.display());
+            }
+        }
+        println!("Logs: journalctl --user -u {}", UNIT_NAME);
+        Ok(())
+    }
+
+    pub fn uninstall() -> Result<(), Box<dyn Error>> {
+        let mut removed = false;
+        let unit = unit_path();
+        if unit.exists() {
+            systemctl(&["disable", "--now", UNIT_NAME]);
+            std::fs::remove_file(&unit)?;
+            systemctl(&["daemon-reload"]);
+            println!("Removed {}", unit.display());
+            removed = true;
+        }
+        let desktop = desktop_path();
+        if desktop.exists() {
+            std::fs::remove_file(&desktop)?;
+            println!("Removed {}", desktop.display());
+            removed = true;
+        }
+        if !removed {
+            println!("Chronos is not set up to start on login.");
+        }
+        Ok(())
+    }
+
+    pub fn status() -> Result<(), Box<dyn Error>> {
+        let unit = unit_path();
+        if unit.exists() {
+            let state = |what: &str| systemctl(&[what, UNIT_NAME]).map(|(_, text)| text).unwrap_or_else(|| "unknown".into());
+            println!("systemd unit:  {} ({}, {})", unit.display(), state("is-enabled"), state("is-active"));
+        } else {
+            println!("systemd unit:  not installed");
+        }
+        let desktop = desktop_path();
+        if desktop.exists() {
+            println!("XDG autostart: {}", desktop.display());
+        } else {
+            println!("XDG autostart: not installed");
+        }
+        match instance::running_instance() {
+            Some(owner) => println!("Tracker:       running (pid {}, since {})", owner.pid, owner.started_at),
+            None => println!("Tracker:       not running"),
+        }
+        Ok(())
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        fn line<'a>(file: &'a str, key: &str) -> &'a str {
+            file.lines().find(|line| line.starts_with(key)).unwrap()
+        }
+
+        #[test]
+        fn unit_exec() {
+            assert_eq!(line(&unit("/home/me/My Apps/chronos"), "ExecStart="), r#"ExecStart="/home/me/My Apps/chronos" run"#);
+            assert_eq!(
+                line(&unit(r#"/opt/a "b" \c/100%/$HOME/chronos"#), "ExecStart="),
+                r#"ExecStart="/opt/a \"b\" \\c/100%%/$$HOME/chronos" run"#
+            );
+        }
+
+        #[test]
+        fn desktop_exec() {
+            assert_eq!(line(&desktop_entry("/home/me/My Apps/chronos"), "Exec="), r#"Exec="/home/me/My Apps/chronos" run"#);
+            assert_eq!(
+                line(&desktop_entry(r#"/opt/a "b" \c/100%/$HOME/`x`/chronos"#), "Exec="),
+                r#"Exec="/opt/a \\"b\\" \\\\c/100%%/\\$HOME/\\`x\\`/chronos" run"#
+            );
+        }
+    }
+}
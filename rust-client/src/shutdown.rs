@@ -0,0 +1,101 @@
+// -------------------- graceful shutdown --------------------
+// SIGTERM/SIGINT/SIGHUP (logout, `systemctl stop`, Ctrl+C, closing the
+// terminal) and the Windows console events stop the tracker instead of
+// killing it mid-session: the main loop ends the current session, records
+// `tracker_stopped` and tries one last sync before returning.
+
+// Waits for a request to stop and names it, e.g. "SIGTERM"
+#[cfg(unix)]
+pub async fn requested() -> &'static str {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    let signals = (signal(SignalKind::terminate()), signal(SignalKind::interrupt()), signal(SignalKind::hangup()));
+    let (mut term, mut int, mut hup) = match signals {
+        (Ok(term), Ok(int), Ok(hup)) => (term, int, hup),
+        _ => {
+            crate::log_line("Could not install signal handlers; stopping will not be graceful");
+            return std::future::pending().await;
+        }
+    };
+    tokio::select! {
+        _ = term.recv() => "SIGTERM",
+        _ = int.recv() => "SIGINT",
+        _ = hup.recv() => "SIGHUP",
+    }
+}
+
+#[cfg(windows)]
+pub async fn requested() -> &'static str {
+    console::requested().await
+}
+
+// Cleanup is done; lets a waiting console handler return
+pub fn finished() {
+    #[cfg(windows)]
+    console::finished();
+}
+
+// tokio's ctrl_close() returns from the handler right away, and Windows ends
+// the process as soon as it does, so closing the console window, logging off
+// and shutting down need a handler that waits for the cleanup (Windows allows
+// about 5 seconds).
+#[cfg(windows)]
+mod console {
+    use std::sync::atomic::{AtomicU32, Ordering};
+    use std::sync::{Condvar, Mutex, OnceLock};
+    use std::time::Duration;
+    use tokio::sync::Notify;
+    use windows::Win32::Foundation::{BOOL, TRUE};
+    use windows::Win32::System::Console::{
+        SetConsoleCtrlHandler, CTRL_BREAK_EVENT, CTRL_CLOSE_EVENT, CTRL_C_EVENT, CTRL_LOGOFF_EVENT, CTRL_SHUTDOWN_EVENT,
+    };
+
+    use crate::log_line;
+
+    // Leave some of the 5 seconds for Windows to tear the process down
+    const CLOSE_GRACE: Duration = Duration::from_millis(4500);
+
+    static STOP: OnceLock<Notify> = OnceLock::new();
+    static EVENT: AtomicU32 = AtomicU32::new(u32::MAX);
+    static DONE: (Mutex<bool>, Condvar) = (Mutex::new(false), Condvar::new());
+
+    fn stop() -> &'static Notify {
+        STOP.get_or_init(Notify::new)
+    }
+
+    unsafe extern "system" fn handler(event: u32) -> BOOL {
+        EVENT.store(event, Ordering::SeqCst);
+        stop().notify_one();
+        if matches!(event, CTRL_CLOSE_EVENT | CTRL_LOGOFF_EVENT | CTRL_SHUTDOWN_EVENT) {
+            let (done, finished) = &DONE;
+            if let Ok(done) = done.lock() {
+                let _ = finished.wait_timeout_while(done, CLOSE_GRACE, |done| !*done);
+            }
+        }
+        TRUE
+    }
+
+    pub async fn requested() -> &'static str {
+        if let Err(e) = unsafe { SetConsoleCtrlHandler(Some(handler), true) } {
+            log_line(&format!("Could not install the console handler; stopping will not be graceful: {}", e));
+            return std::future::pending().await;
+        }
+        stop().notified().await;
+        match EVENT.load(Ordering::SeqCst) {
+            CTRL_C_EVENT => "Ctrl+C",
+            CTRL_BREAK_EVENT => "Ctrl+Break",
+            CTRL_CLOSE_EVENT => "console closed",
+            CTRL_LOGOFF_EVENT => "logoff",
+            CTRL_SHUTDOWN_EVENT => "shutdown",
+            _ => "console event",
+        }
+    }
+
+    pub fn finished() {
+        let (done, finished) = &DONE;
+        if let Ok(mut done) = done.lock() {
+            *done = true;
+            finished.notify_all();
+        }
+    }
+}
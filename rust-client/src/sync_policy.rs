@@ -0,0 +1,307 @@
+use chrono::{Duration, NaiveDateTime, Timelike};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::{BTreeMap, HashMap};
+
+use crate::config::Config;
+use crate::entries::{data_str, entry_time, format_timestamp, window_sessions};
+use crate::issues::IssueDetector;
+use crate::redact::{RedactionConfig, Redactor};
+use crate::urls::domain_of;
+use crate::LogEntry;
+
+// -------------------- sync data minimisation --------------------
+// The activity log keeps full detail locally; the sync level decides how much
+// of it leaves the machine.
+//
+//   full     - entries as recorded
+//   redacted - titles, paths and project names redacted, command lines left
+//              out, URLs reduced to the domain
+//   apps     - no titles, URLs or domains: seconds per app and category per
+//              hour, sent once the hour is over
+//
+// An entry's level is that of its category (see categories; the longest
+// matching prefix, so "Personal" covers "Personal > Social"), else that of
+// its entry type ("window", "browser"), else "level".
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "lowercase")]
+pub enum SyncLevel {
+    Apps,
+    Redacted,
+    #[default]
+    Full,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(default, rename_all = "camelCase")]
+pub struct SyncConfig {
+    pub level: SyncLevel,
+    // By entry type, e.g. { "browser": "redacted" }
+    pub types: HashMap<String, SyncLevel>,
+    // By category, e.g. { "Personal": "apps", "Work > Coding": "full" }
+    pub categories: HashMap<String, SyncLevel>,
+}
+
+impl SyncConfig {
+    pub fn level_for(&self, entry: &LogEntry) -> SyncLevel {
+        let category = data_str(entry, "category");
+        let by_category = self
+            .categories
+            .iter()
+            .filter(|(prefix, _)| category == prefix.as_str() || category.starts_with(&format!("{} > ", prefix)))
+            .max_by_key(|(prefix, _)| prefix.len())
+            .map(|(_, level)| *level);
+        by_category.or_else(|| self.types.get(&entry.log_type).copied()).unwrap_or(self.level)
+    }
+}
+
+pub struct SyncPolicy {
+    config: SyncConfig,
+    // Always-on redactor for the "redacted" level, even when local
+    // redaction is switched off
+    redactor: Redactor,
+    issues: IssueDetector,
+}
+
+// At the "redacted" level, only the issues the redacted title (and the
+// branch) still show: the full title or URL may have named more
+fn set_issues(data: &mut Map<String, Value>, issues: Vec<String>) {
+    if issues.is_empty() {
+        data.remove("issues");
+    } else {
+        data.insert("issues".to_string(), Value::from(issues));
+    }
+}
+
+fn hour_of(t: NaiveDateTime) -> NaiveDateTime {
+    t.with_minute(0).and_then(|t| t.with_second(0)).and_then(|t| t.with_nanosecond(0)).unwrap_or(t)
+}
+
+impl SyncPolicy {
+    pub fn from_config(config: &Config) -> SyncPolicy {
+        SyncPolicy {
+            config: config.sync.clone(),
+            redactor: Redactor::from_config(&RedactionConfig { enabled: true, ..config.redaction.clone() }),
+            issues: IssueDetector::from_config(config),
+        }
+    }
+
+    // Reduce entries to what the configured levels allow. Entries should
+    // already carry their category. App time is aggregated for the hours from
+    // `apps_sent_until` (the start of the first hour not sent yet) that are
+    // over; the second value is where the next sync continues.
+    pub fn minimise(&self, entries: Vec<LogEntry>, apps_sent_until: Option<NaiveDateTime>, now: NaiveDateTime) -> (Vec<LogEntry>, NaiveDateTime) {
+        // the open session (the last window entry, unless a tracker event
+        // followed it) may still add to its hour
+        let open_since = entries
+            .iter()
+            .filter(|e| e.log_type == "window" || e.log_type == "tracker")
+            .filter_map(|e| entry_time(e).map(|t| (t, e.log_type == "window")))
+            .max_by_key(|(t, _)| *t)
+            .filter(|(_, open)| *open)
+            .map_or(now, |(t, _)| t);
+        let until = hour_of(now.min(open_since));
+        let apps: Vec<usize> = (0..entries.len())
+            .filter(|i| entries[*i].log_type == "window" && self.config.level_for(&entries[*i]) == SyncLevel::Apps)
+            .collect();
+        let mut out = aggregate_app_time(&entries, &apps, apps_sent_until, until);
+
+        for mut entry in entries {
+            let level = self.config.level_for(&entry);
+            match (entry.log_type.as_str(), level) {
+                // Tracker events only mark session boundaries locally
+                ("tracker", _) => continue,
+                ("window" | "browser", SyncLevel::Apps) => continue,
+                (_, SyncLevel::Full) => {}
+                ("window", SyncLevel::Redacted) => {
+                    let title = self.redactor.redact(data_str(&entry, "windowTitle"));
+                    let redacted: Vec<(&str, String)> = ["exePath", "workingDirectory", "repositoryPath", "project", "file"]
+                        .into_iter()
+                        .filter(|key| entry.data.get(*key).is_some())
+                        .map(|key| (key, self.redactor.redact(data_str(&entry, key))))
+                        .collect();
+                    let branch = entry.data.get("branch").and_then(|v| v.as_str());
+                    let issues = self.issues.find(&title, None, branch);
+                    if let Value::Object(data) = &mut entry.data {
+                        set_issues(data, issues);
+                        data.insert("windowTitle".to_string(), Value::String(title));
+                        // arguments are too often file names and URLs
+                        data.remove("commandLine");
+                        data.remove("foregroundCommand");
+                        for (key, text) in redacted {
+                            data.insert(key.to_string(), Value::String(text));
+                        }
+                    }
+                }
+                ("browser", SyncLevel::Redacted) => {
+                    let title = self.redactor.redact(data_str(&entry, "browserTitle"));
+                    let domain = domain_of(data_str(&entry, "url"));
+                    let issues = self.issues.find(&title, None, None);
+                    if let Value::Object(data) = &mut entry.data {
+                        set_issues(data, issues);
+                        data.insert("browserTitle".to_string(), Value::String(title));
+                        match domain {
+                            Some(domain) => data.insert("url".to_string(), Value::String(format!("https://{}/", domain))),
+                            None => data.remove("url"),
+                        };
+                    }
+                }
+                // Unknown entry types only go out in full
+                _ => continue,
+            }
+            out.push(entry);
+        }
+
+        out.sort_by(|a, b| a.timestamp.cmp(&b.timestamp));
+        (out, until.max(apps_sent_until.unwrap_or(until)))
+    }
+}
+
+// (category, productivity) as set by the category rules (see categories)
+type Category = (String, Option<i64>);
+
+fn category_of(entry: &LogEntry) -> Category {
+    (data_str(entry, "category").to_string(), entry.data.get("productivity").and_then(|v| v.as_i64()))
+}
+
+fn insert_category(data: &mut Map<String, Value>, (category, productivity): Category) {
+    if !category.is_empty() {
+        data.insert("category".to_string(), Value::String(category));
+    }
+    if let Some(productivity) = productivity {
+        data.insert("productivity".to_string(), Value::from(productivity));
+    }
+}
+
+// One "window" entry per app and category per hour with the focused seconds,
+// no titles, for the window entries `apps` and the hours in [from, until).
+// Processes the app rules tell apart (two java IDEs) stay apart.
+fn aggregate_app_time(entries: &[LogEntry], apps: &[usize], from: Option<NaiveDateTime>, until: NaiveDateTime) -> Vec<LogEntry> {
+    // milliseconds until the end, so many short sessions don't round away
+    let mut totals: BTreeMap<(NaiveDateTime, String, String, Category), i64> = BTreeMap::new();
+    for (i, start, end) in window_sessions(entries).into_iter().filter(|(i, _, _)| apps.contains(i)) {
+        let process = data_str(&entries[i], "processName").to_string();
+        let app = data_str(&entries[i], "app").to_string();
+        let category = category_of(&entries[i]);
+        // split sessions at hour boundaries
+        let mut from_time = start.max(from.unwrap_or(start));
+        let end = end.min(until);
+        while from_time < end {
+            let bucket = hour_of(from_time);
+            let next = (bucket + Duration::hours(1)).min(end);
+            *totals.entry((bucket, process.clone(), app.clone(), category.clone())).or_insert(0) += (next - from_time).num_milliseconds();
+            from_time = next;
+        }
+    }
+
+    totals
+        .into_iter()
+        .map(|(key, millis)| (key, (millis + 500) / 1000))
+        .filter(|(_, seconds)| *seconds > 0)
+        .map(|((hour, process, app, category), seconds)| {
+            let mut data = Map::new();
+            data.insert("processName".to_string(), Value::String(process));
+            if !app.is_empty() {
+                data.insert("app".to_string(), Value::String(app));
+            }
+            insert_category(&mut data, category);
+            data.insert("durationSeconds".to_string(), Value::from(seconds));
+            LogEntry {
+                timestamp: format_timestamp(hour),
+                log_type: "window".to_string(),
+                data: Value::Object(data),
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::entries::parse_timestamp;
+    use serde_json::json;
+
+    fn entry(timestamp: &str, log_type: &str, data: Value) -> LogEntry {
+        LogEntry { timestamp: timestamp.to_string(), log_type: log_type.to_string(), data }
+    }
+
+    fn window(timestamp: &str, process: &str, category: &str) -> LogEntry {
+        entry(timestamp, "window", json!({ "processName": process, "windowTitle": "secret.txt", "category": category }))
+    }
+
+    fn time(text: &str) -> NaiveDateTime {
+        parse_timestamp(text).unwrap()
+    }
+
+    // (hour, process, seconds) of aggregated entries
+    fn rows(entries: &[LogEntry]) -> Vec<(&str, &str, i64)> {
+        entries.iter().map(|e| (e.timestamp.as_str(), data_str(e, "processName"), e.data["durationSeconds"].as_i64().unwrap())).collect()
+    }
+
+    fn policy(config: SyncConfig) -> SyncPolicy {
+        SyncPolicy { config, redactor: Redactor::from_config(&RedactionConfig::default()), issues: IssueDetector::from_config(&Config::default()) }
+    }
+
+    #[test]
+    fn level_by_category_then_type() {
+        let config = SyncConfig {
+            level: SyncLevel::Full,
+            types: HashMap::from([("window".to_string(), SyncLevel::Redacted)]),
+            categories: HashMap::from([
+                ("Personal".to_string(), SyncLevel::Apps),
+                ("Personal > Shopping".to_string(), SyncLevel::Redacted),
+            ]),
+        };
+        let level = |log_type: &str, category: &str| config.level_for(&entry("", log_type, json!({ "category": category })));
+        assert_eq!(level("window", "Personal > Social"), SyncLevel::Apps);
+        assert_eq!(level("browser", "Personal > Shopping > Books"), SyncLevel::Redacted);
+        assert_eq!(level("browser", "Personalised"), SyncLevel::Full);
+        assert_eq!(level("window", "Work"), SyncLevel::Redacted);
+        assert_eq!(level("browser", ""), SyncLevel::Full);
+    }
+
+    #[test]
+    fn apps_level_sends_closed_hours_once() {
+        let policy = policy(SyncConfig { level: SyncLevel::Apps, ..Default::default() });
+        let entries = vec![
+            window("2026-03-02 09:30:00", "code", "Work > Coding"),
+            entry("2026-03-02 09:40:00", "browser", json!({ "browserTitle": "Bank", "url": "https://bank.example/" })),
+            window("2026-03-02 10:15:00", "slack", "Work > Communication"),
+            window("2026-03-02 11:20:00", "code", "Work > Coding"),
+        ];
+
+        // 11:00 isn't over yet
+        let (sent, until) = policy.minimise(entries.clone(), None, time("2026-03-02 11:30:00"));
+        assert_eq!(until, time("2026-03-02 11:00:00"));
+        assert_eq!(rows(&sent), vec![("2026-03-02 09:00:00", "code", 1800), ("2026-03-02 10:00:00", "code", 900), ("2026-03-02 10:00:00", "slack", 2700)]);
+        assert!(sent.iter().all(|e| e.data.get("windowTitle").is_none()));
+
+        // nothing new until the open session ends and its hour is over
+        let (sent, until) = policy.minimise(entries.clone(), Some(until), time("2026-03-02 13:05:00"));
+        assert!(sent.is_empty());
+        assert_eq!(until, time("2026-03-02 11:00:00"));
+
+        let mut entries = entries;
+        entries.push(entry("2026-03-02 11:50:00", "tracker", json!({ "event": "paused" })));
+        let (sent, until) = policy.minimise(entries, Some(until), time("2026-03-02 13:05:00"));
+        assert_eq!(rows(&sent), vec![("2026-03-02 11:00:00", "code", 1800), ("2026-03-02 11:00:00", "slack", 1200)]);
+        assert_eq!(until, time("2026-03-02 13:00:00"));
+    }
+
+    #[test]
+    fn redacted_issues() {
+        let policy = policy(SyncConfig { level: SyncLevel::Redacted, ..Default::default() });
+        let entries = vec![
+            // only the URL names the issue, and only the domain is sent
+            entry("2026-03-02 09:00:00", "browser", json!({ "browserTitle": "Fix login", "url": "https://github.com/acme/chronos/issues/45", "issues": ["acme/chronos#45"] })),
+            entry("2026-03-02 09:01:00", "browser", json!({ "browserTitle": "PROJ-7 Invoice for jane@example.com", "url": "https://acme.atlassian.net/browse/PROJ-7", "issues": ["PROJ-7"] })),
+            entry("2026-03-02 09:02:00", "window", json!({ "windowTitle": "main.rs - chronos", "branch": "feature/PROJ-5-sync", "issues": ["PROJ-5"] })),
+        ];
+        let (sent, _) = policy.minimise(entries, None, time("2026-03-02 09:03:00"));
+        let issues: Vec<&Value> = sent.iter().map(|e| &e.data["issues"]).collect();
+        assert_eq!(issues, [&Value::Null, &json!(["PROJ-7"]), &json!(["PROJ-5"])]);
+        assert_eq!(sent[0].data["url"], "https://github.com/");
+        assert_eq!(sent[1].data["browserTitle"], "PROJ-7 Invoice for [email]");
+    }
+}
@@ -0,0 +1,52 @@
+use chrono::Local;
+use serde::{Deserialize, Serialize};
+
+use crate::{get_app_data_dir, log_line};
+
+// -------------------- sync status --------------------
+// What the sync task last saw, kept in sync_status.json so that
+// `chronos status` (a separate process) can report it.
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(default, rename_all = "camelCase")]
+pub struct SyncStatus {
+    // Local "%Y-%m-%d %H:%M:%S" timestamps
+    pub last_sync: Option<String>,
+    pub last_error: Option<String>,
+    // Set when the server answered 401. Syncing waits for a new sign-in;
+    // activity is still recorded locally.
+    pub token_rejected_at: Option<String>,
+    // Hourly app time (the "apps" sync level) has been sent for the hours
+    // before this one
+    pub apps_synced_until: Option<String>,
+}
+
+pub fn now() -> String {
+    Local::now().format("%Y-%m-%d %H:%M:%S").to_string()
+}
+
+fn status_path() -> std::path::PathBuf {
+    get_app_data_dir().join("sync_status.json")
+}
+
+impl SyncStatus {
+    pub fn load() -> SyncStatus {
+        std::fs::read_to_string(status_path())
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self) {
+        let result = serde_json::to_string_pretty(self)
+            .map_err(|e| e.to_string())
+            .and_then(|json| std::fs::write(status_path(), json).map_err(|e| e.to_string()));
+        if let Err(e) = result {
+            log_line(&format!("Could not save sync status: {}", e));
+        }
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.token_rejected_at.is_some()
+    }
+}
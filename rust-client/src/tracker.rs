@@ -0,0 +1,82 @@
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use tokio::sync::{mpsc, oneshot, Notify};
+
+use crate::LogEntry;
+
+// -------------------- live tracker state --------------------
+// What the running tracker is doing right now, shared between the main loop,
+// the sync task and the control API (see ipc).
+
+// How many recorded events to keep for `chronos events`
+const RECENT_EVENTS: usize = 100;
+
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Session {
+    pub title: String,
+    pub process: String,
+    // From the app rules (see process), e.g. "PyCharm" for a java process
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub app: Option<String>,
+    // The git repository and branch of the window (see git)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub repository: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub branch: Option<String>,
+    // Issue keys in the title or branch (see issues)
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub issues: Vec<String>,
+    // From the category rules (see categories)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub category: Option<String>,
+    // Local "%Y-%m-%d %H:%M:%S" timestamp
+    pub since: String,
+}
+
+#[derive(Default)]
+pub struct TrackerState {
+    pub started_at: String,
+    // The window being recorded, None while paused or nothing is focused
+    pub session: Option<Session>,
+    pub recent: VecDeque<LogEntry>,
+}
+
+impl TrackerState {
+    pub fn record(&mut self, entry: LogEntry) {
+        self.recent.push_back(entry);
+        while self.recent.len() > RECENT_EVENTS {
+            self.recent.pop_front();
+        }
+    }
+}
+
+// A sync asked for through the control API; the sync task answers on the
+// channel once it is done
+pub type SyncRequest = oneshot::Sender<Result<(), String>>;
+
+#[derive(Clone)]
+pub struct Tracker {
+    pub state: Arc<Mutex<TrackerState>>,
+    pub sync_now: mpsc::Sender<SyncRequest>,
+    // Wakes the main loop early, e.g. after pause/resume
+    pub wake: Arc<Notify>,
+}
+
+impl Tracker {
+    pub fn new(started_at: String) -> (Tracker, mpsc::Receiver<SyncRequest>) {
+        let (sync_now, sync_requests) = mpsc::channel(4);
+        let state = TrackerState { started_at, ..Default::default() };
+        let tracker = Tracker { state: Arc::new(Mutex::new(state)), sync_now, wake: Arc::new(Notify::new()) };
+        (tracker, sync_requests)
+    }
+
+    pub fn record(&self, entry: LogEntry) {
+        self.state.lock().unwrap().record(entry);
+    }
+
+    pub fn set_session(&self, session: Option<Session>) {
+        self.state.lock().unwrap().session = session;
+    }
+}
@@ -0,0 +1,287 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+// -------------------- URL normalisation --------------------
+// Browser history gives us URLs verbatim, including query strings with
+// session tokens, search terms and tracking ids. Every URL goes through
+// normalize_url() before it is written to the activity log or synced.
+
+// Query parameters that only exist for ad/analytics attribution
+const TRACKING_PARAMS: &[&str] = &[
+    "fbclid", "gclid", "gclsrc", "dclid", "gbraid", "wbraid", "msclkid", "yclid", "twclid",
+    "ttclid", "igshid", "li_fat_id", "mc_cid", "mc_eid", "_ga", "_gl", "_hsenc", "_hsmi",
+    "__hssc", "__hstc", "__hsfp", "hsctatracking", "mkt_tok", "oly_anon_id", "oly_enc_id",
+    "vero_id", "ref_src", "ref_url", "spm", "scid", "s_cid", "srsltid",
+];
+const TRACKING_PREFIXES: &[&str] = &["utm_", "pk_", "mtm_"];
+
+// Query parameters that commonly carry credentials or session state
+const SENSITIVE_PARAMS: &[&str] = &[
+    "access_token", "id_token", "refresh_token", "token", "auth", "auth_token", "code",
+    "session", "sessionid", "session_id", "sid", "jsessionid", "phpsessid", "password",
+    "passwd", "pwd", "apikey", "api_key", "signature", "sig", "x-amz-signature",
+    "x-amz-credential", "x-amz-security-token",
+];
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default, rename_all = "camelCase")]
+pub struct UrlConfig {
+    // Remove utm_*, fbclid, gclid and friends
+    pub strip_tracking_params: bool,
+    // Remove token/session/password style parameters
+    pub strip_sensitive_params: bool,
+    // Extra parameter names to remove everywhere
+    pub strip_params: Vec<String>,
+    // Record only "https://example.com/" for every URL
+    pub domain_only: bool,
+    // Treat http:// and https:// as the same page
+    pub normalize_scheme: bool,
+    // Treat www.example.com and example.com as the same site
+    pub strip_www: bool,
+    // Per-domain overrides, keyed by domain (also applies to subdomains)
+    pub domains: HashMap<String, DomainUrlRule>,
+}
+
+impl Default for UrlConfig {
+    fn default() -> Self {
+        UrlConfig {
+            strip_tracking_params: true,
+            strip_sensitive_params: true,
+            strip_params: Vec::new(),
+            domain_only: false,
+            normalize_scheme: true,
+            strip_www: true,
+            domains: HashMap::new(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(default, rename_all = "camelCase")]
+pub struct DomainUrlRule {
+    // Overrides the global domainOnly setting for this domain
+    pub domain_only: Option<bool>,
+    // Drop the whole query string (e.g. search engines)
+    pub drop_query: bool,
+    // Extra parameter names to remove for this domain
+    pub strip_params: Vec<String>,
+}
+
+impl UrlConfig {
+    // Most specific rule whose domain equals `host` or is a parent of it
+    fn rule_for(&self, host: &str) -> Option<&DomainUrlRule> {
+        self.domains
+            .iter()
+            .filter(|(domain, _)| {
+                let domain = domain.trim_start_matches("www.").to_lowercase();
+                host == domain || host.ends_with(&format!(".{}", domain))
+            })
+            .max_by_key(|(domain, _)| domain.len())
+            .map(|(_, rule)| rule)
+    }
+
+    fn strips_param(&self, rule: Option<&DomainUrlRule>, name: &str) -> bool {
+        let name = name.to_lowercase();
+        (self.strip_tracking_params
+            && (TRACKING_PARAMS.contains(&name.as_str()) || TRACKING_PREFIXES.iter().any(|p| name.starts_with(p))))
+            || (self.strip_sensitive_params && SENSITIVE_PARAMS.contains(&name.as_str()))
+            || self.strip_params.iter().any(|p| p.eq_ignore_ascii_case(&name))
+            || rule.is_some_and(|r| r.strip_params.iter().any(|p| p.eq_ignore_ascii_case(&name)))
+    }
+
+    // Whether URLs for this host are reduced to the bare domain
+    fn is_domain_only(&self, url: &str) -> bool {
+        match domain_of(url) {
+            Some(host) => {
+                let host = self.normalize_host(&host);
+                self.rule_for(&host).and_then(|r| r.domain_only).unwrap_or(self.domain_only)
+            }
+            None => self.domain_only,
+        }
+    }
+
+    fn normalize_host(&self, host: &str) -> String {
+        let host = host.trim_end_matches('.').to_lowercase();
+        if self.strip_www {
+            host.strip_prefix("www.").map(str::to_string).unwrap_or(host)
+        } else {
+            host
+        }
+    }
+}
+
+// Domain as written in a rule, for matching against domain_of
+// ("WWW.Example.com." -> "example.com")
+pub fn bare_domain(domain: &str) -> String {
+    let domain = domain.trim().trim_end_matches('.').to_lowercase();
+    domain.strip_prefix("www.").map(str::to_string).unwrap_or(domain)
+}
+
+// Extract the host part of a URL ("https://user@www.example.com:8080/a?b" -> "www.example.com").
+// Only web pages have one: about:blank, mailto: and chrome:// URLs don't.
+pub fn domain_of(url: &str) -> Option<String> {
+    let (scheme, rest) = url.trim().split_once("://")?;
+    if !scheme.eq_ignore_ascii_case("http") && !scheme.eq_ignore_ascii_case("https") {
+        return None;
+    }
+    let authority = rest.split(['/', '?', '#']).next()?;
+    let host_port = authority.rsplit('@').next()?;
+    let host = if host_port.starts_with('[') {
+        // IPv6 literal, keep the brackets
+        host_port.split(']').next().map(|h| format!("{}]", h))?
+    } else {
+        host_port.split(':').next()?.to_string()
+    };
+    let host = host.trim_end_matches('.').to_lowercase();
+    if host.is_empty() { None } else { Some(host) }
+}
+
+pub fn normalize_url(url: &str, config: &UrlConfig) -> String {
+    let url = url.trim();
+    // Only hierarchical URLs (http, https, ftp, file, ...) are rewritten
+    let Some((scheme, rest)) = url.split_once("://") else {
+        return url.to_string();
+    };
+    let original_scheme = scheme.to_lowercase();
+    let scheme = if config.normalize_scheme && original_scheme == "http" {
+        "https".to_string()
+    } else {
+        original_scheme.clone()
+    };
+
+    // Fragments often hold OAuth tokens (#access_token=...), never keep them
+    let rest = rest.split('#').next().unwrap_or("");
+    let (before_query, query) = match rest.split_once('?') {
+        Some((b, q)) => (b, Some(q)),
+        None => (rest, None),
+    };
+    let (authority, path) = match before_query.find('/') {
+        Some(i) => (&before_query[..i], &before_query[i..]),
+        None => (before_query, "/"),
+    };
+
+    // Drop credentials and default ports
+    let host_port = authority.rsplit('@').next().unwrap_or("");
+    let (host, port) = match host_port.rsplit_once(':') {
+        Some((h, p)) if !p.contains(']') && p.chars().all(|c| c.is_ascii_digit()) => (h, Some(p)),
+        _ => (host_port, None),
+    };
+    let host = config.normalize_host(host);
+    let port = port.filter(|p| !matches!((original_scheme.as_str(), *p), ("https", "443") | ("http", "80") | (_, "")));
+    let origin = match port {
+        Some(port) => format!("{}://{}:{}", scheme, host, port),
+        None => format!("{}://{}", scheme, host),
+    };
+
+    let rule = config.rule_for(&host);
+    if rule.and_then(|r| r.domain_only).unwrap_or(config.domain_only) {
+        return format!("{}/", origin);
+    }
+
+    let query = query.filter(|_| !rule.is_some_and(|r| r.drop_query)).map(|q| {
+        q.split('&')
+            .filter(|pair| !pair.is_empty())
+            .filter(|pair| {
+                let name = pair.split('=').next().unwrap_or("");
+                !config.strips_param(rule, name)
+            })
+            .collect::<Vec<&str>>()
+            .join("&")
+    });
+
+    match query {
+        Some(q) if !q.is_empty() => format!("{}{}?{}", origin, path, q),
+        _ => format!("{}{}", origin, path),
+    }
+}
+
+// Normalise a browser visit. In domain-only mode the page title is replaced
+// with the domain too, since titles usually give away the path or search.
+pub fn sanitize_visit(url: &str, title: &str, config: &UrlConfig) -> (String, String) {
+    let normalized = normalize_url(url, config);
+    if config.is_domain_only(url) {
+        let title = domain_of(&normalized).unwrap_or_default();
+        (normalized, title)
+    } else {
+        (normalized, title.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn domains() {
+        assert_eq!(domain_of("https://user@www.Example.com:8080/a?b").as_deref(), Some("www.example.com"));
+        assert_eq!(domain_of("http://example.com.").as_deref(), Some("example.com"));
+        assert_eq!(domain_of("https://[::1]:3000/").as_deref(), Some("[::1]"));
+        for url in ["about:blank", "mailto:x@y.com", "chrome://settings", "file:///home/me/a.html", "example.com/page", "https:///"] {
+            assert_eq!(domain_of(url), None, "{}", url);
+        }
+        assert_eq!(bare_domain(" WWW.Example.com. "), "example.com");
+    }
+
+    #[test]
+    fn query_params() {
+        let config = UrlConfig::default();
+        let cases = [
+            ("https://example.com/a?utm_source=x&id=3&fbclid=y", "https://example.com/a?id=3"),
+            ("https://example.com/cb?code=abc&state=1&access_token=t", "https://example.com/cb?state=1"),
+            ("https://example.com/?utm_campaign=x", "https://example.com/"),
+            ("https://example.com/a#access_token=t", "https://example.com/a"),
+            ("http://user:pw@www.example.com:80/a", "https://example.com/a"),
+            ("https://example.com:8443", "https://example.com:8443/"),
+            ("about:blank", "about:blank"),
+        ];
+        for (url, expected) in cases {
+            assert_eq!(normalize_url(url, &config), expected, "{}", url);
+        }
+
+        let config = UrlConfig {
+            strip_tracking_params: false,
+            strip_params: vec!["Ref".to_string()],
+            normalize_scheme: false,
+            strip_www: false,
+            ..UrlConfig::default()
+        };
+        assert_eq!(normalize_url("http://www.example.com/?utm_source=x&ref=y", &config), "http://www.example.com/?utm_source=x");
+    }
+
+    #[test]
+    fn domain_rules() {
+        let mut config = UrlConfig::default();
+        config.domains.insert("google.com".to_string(), DomainUrlRule { drop_query: true, ..Default::default() });
+        config.domains.insert("example.com".to_string(), DomainUrlRule { strip_params: vec!["page".to_string()], ..Default::default() });
+        config.domains.insert("www.bank.com".to_string(), DomainUrlRule { domain_only: Some(true), ..Default::default() });
+        assert_eq!(normalize_url("https://www.google.com/search?q=secret", &config), "https://google.com/search");
+        assert_eq!(normalize_url("https://docs.example.com/a?page=2&lang=en", &config), "https://docs.example.com/a?lang=en");
+        assert_eq!(normalize_url("https://notexample.com/a?page=2", &config), "https://notexample.com/a?page=2");
+
+        // domain-only for one site: the title would give the page away
+        assert_eq!(
+            sanitize_visit("https://online.bank.com/accounts/123?x=1", "Account 123 - Bank", &config),
+            ("https://online.bank.com/".to_string(), "online.bank.com".to_string())
+        );
+        assert_eq!(
+            sanitize_visit("https://example.com/a", "A page", &config),
+            ("https://example.com/a".to_string(), "A page".to_string())
+        );
+    }
+
+    #[test]
+    fn domain_only_mode() {
+        let mut config = UrlConfig { domain_only: true, ..UrlConfig::default() };
+        assert_eq!(
+            sanitize_visit("https://www.example.com/private/page?id=1", "Private page", &config),
+            ("https://example.com/".to_string(), "example.com".to_string())
+        );
+        // a domain can opt out
+        config.domains.insert("docs.rs".to_string(), DomainUrlRule { domain_only: Some(false), ..Default::default() });
+        assert_eq!(
+            sanitize_visit("https://docs.rs/serde", "serde - Rust", &config),
+            ("https://docs.rs/serde".to_string(), "serde - Rust".to_string())
+        );
+        assert_eq!(sanitize_visit("about:blank", "New Tab", &config), ("about:blank".to_string(), String::new()));
+    }
+}
@@ -0,0 +1,17 @@
+import jwt from 'jsonwebtoken';
+
+// Lifetime of tokens issued to the desktop client
+export const CLIENT_TOKEN_TTL_SECONDS = 30 * 24 * 60 * 60; // 30 days
+
+// Sign a long-lived token for the Rust client
+export function signClientToken(user) {
+  return jwt.sign(
+    {
+      userId: user._id,
+      email: user.email,
+      type: 'rust-client'
+    },
+    process.env.JWT_SECRET,
+    { expiresIn: CLIENT_TOKEN_TTL_SECONDS }
+  );
+}
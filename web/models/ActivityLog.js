@@ -21,6 +21,11 @@ const ActivityLogSchema = new mongoose.Schema({
     url: String,
     browserTitle: String,
     browserName: String,
+    browserType: String,
+    visitedAt: Date,
+    domain: String,
+    focusSeconds: Number,
+    durationSeconds: Number,
   }
 }, {
   timestamps: true,
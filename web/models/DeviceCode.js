@@ -0,0 +1,40 @@
+import mongoose from 'mongoose';
+
+// Pending device-authorization requests (RFC 8628) from headless clients
+const DeviceCodeSchema = new mongoose.Schema({
+  deviceCode: {
+    type: String,
+    required: true,
+    unique: true,
+  },
+  userCode: {
+    type: String,
+    required: true,
+    unique: true,
+  },
+  status: {
+    type: String,
+    enum: ['pending', 'approved', 'denied', 'used'],
+    default: 'pending',
+  },
+  userId: {
+    type: mongoose.Schema.Types.ObjectId,
+    ref: 'User',
+  },
+  interval: {
+    type: Number,
+    default: 5,
+  },
+  lastPolledAt: Date,
+  expiresAt: {
+    type: Date,
+    required: true,
+  },
+}, {
+  timestamps: true,
+});
+
+// Let MongoDB clean up expired requests
+DeviceCodeSchema.index({ expiresAt: 1 }, { expireAfterSeconds: 0 });
+
+export default mongoose.models.DeviceCode || mongoose.model('DeviceCode', DeviceCodeSchema);
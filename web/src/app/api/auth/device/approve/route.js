@@ -0,0 +1,50 @@
+import { NextResponse } from 'next/server';
+import { getServerSession } from 'next-auth';
+import { authOptions } from '@/app/api/auth/[...nextauth]/route';
+import connectDB from '@/../lib/mongodb';
+import DeviceCode from '@/../models/DeviceCode';
+import User from '@/../models/User';
+
+// "bcdf ghjk" / "BCDFGHJK" -> "BCDF-GHJK"
+function normalizeUserCode(code) {
+  const chars = (code || '').toUpperCase().replace(/[^A-Z]/g, '');
+  return chars.length === 8 ? `${chars.slice(0, 4)}-${chars.slice(4)}` : null;
+}
+
+// Approve or deny a device code entered on the /device page
+export async function POST(request) {
+  try {
+    const session = await getServerSession(authOptions);
+
+    if (!session) {
+      return NextResponse.json({ error: 'Unauthorized' }, { status: 401 });
+    }
+
+    const body = await request.json();
+    const userCode = normalizeUserCode(body.userCode);
+    if (!userCode) {
+      return NextResponse.json({ error: 'Invalid code' }, { status: 400 });
+    }
+
+    await connectDB();
+
+    const user = await User.findOne({ email: session.user.email });
+    if (!user) {
+      return NextResponse.json({ error: 'User not found' }, { status: 404 });
+    }
+
+    const record = await DeviceCode.findOne({ userCode, status: 'pending', expiresAt: { $gt: new Date() } });
+    if (!record) {
+      return NextResponse.json({ error: 'Code not found or expired' }, { status: 404 });
+    }
+
+    record.status = body.action === 'deny' ? 'denied' : 'approved';
+    record.userId = user._id;
+    await record.save();
+
+    return NextResponse.json({ status: record.status });
+  } catch (error) {
+    console.error('Error approving device:', error);
+    return NextResponse.json({ error: 'Internal server error' }, { status: 500 });
+  }
+}
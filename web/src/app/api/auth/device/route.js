@@ -0,0 +1,58 @@
+import { NextResponse } from 'next/server';
+import crypto from 'crypto';
+import connectDB from '@/../lib/mongodb';
+import DeviceCode from '@/../models/DeviceCode';
+
+const DEVICE_CODE_TTL_SECONDS = 15 * 60;
+const POLL_INTERVAL_SECONDS = 5;
+
+// No vowels or look-alike characters, so codes can't spell words or be misread
+const USER_CODE_ALPHABET = 'BCDFGHJKLMNPQRSTVWXZ';
+
+function generateUserCode() {
+  const bytes = crypto.randomBytes(8);
+  const chars = Array.from(bytes, (b) => USER_CODE_ALPHABET[b % USER_CODE_ALPHABET.length]).join('');
+  return `${chars.slice(0, 4)}-${chars.slice(4)}`;
+}
+
+// Start a device-authorization request (RFC 8628) for a headless client
+export async function POST(request) {
+  try {
+    await connectDB();
+
+    const baseUrl = process.env.NEXTAUTH_URL || new URL(request.url).origin;
+    const deviceCode = crypto.randomBytes(32).toString('base64url');
+
+    let userCode;
+    for (let attempt = 0; attempt < 5; attempt++) {
+      const candidate = generateUserCode();
+      if (!(await DeviceCode.exists({ userCode: candidate }))) {
+        userCode = candidate;
+        break;
+      }
+    }
+    if (!userCode) {
+      return NextResponse.json({ error: 'Could not allocate a user code' }, { status: 503 });
+    }
+
+    await DeviceCode.create({
+      deviceCode,
+      userCode,
+      interval: POLL_INTERVAL_SECONDS,
+      expiresAt: new Date(Date.now() + DEVICE_CODE_TTL_SECONDS * 1000),
+    });
+
+    const verificationUri = `${baseUrl}/device`;
+    return NextResponse.json({
+      device_code: deviceCode,
+      user_code: userCode,
+      verification_uri: verificationUri,
+      verification_uri_complete: `${verificationUri}?user_code=${encodeURIComponent(userCode)}`,
+      expires_in: DEVICE_CODE_TTL_SECONDS,
+      interval: POLL_INTERVAL_SECONDS,
+    });
+  } catch (error) {
+    console.error('Error starting device authorization:', error);
+    return NextResponse.json({ error: 'Internal server error' }, { status: 500 });
+  }
+}
@@ -0,0 +1,77 @@
+import { NextResponse } from 'next/server';
+import connectDB from '@/../lib/mongodb';
+import DeviceCode from '@/../models/DeviceCode';
+import User from '@/../models/User';
+import { CLIENT_TOKEN_TTL_SECONDS, signClientToken } from '@/../lib/clientToken';
+
+const SLOW_DOWN_INCREMENT_SECONDS = 5;
+
+function oauthError(error, description) {
+  return NextResponse.json({ error, error_description: description }, { status: 400 });
+}
+
+async function readDeviceCode(request) {
+  const contentType = request.headers.get('content-type') || '';
+  if (contentType.includes('application/x-www-form-urlencoded')) {
+    const form = new URLSearchParams(await request.text());
+    return form.get('device_code');
+  }
+  const body = await request.json();
+  return body.device_code;
+}
+
+// Token endpoint polled by the desktop client during the device flow
+export async function POST(request) {
+  try {
+    const deviceCode = await readDeviceCode(request);
+    if (!deviceCode) {
+      return oauthError('invalid_request', 'device_code is required');
+    }
+
+    await connectDB();
+
+    const record = await DeviceCode.findOne({ deviceCode });
+    if (!record || record.expiresAt < new Date()) {
+      return oauthError('expired_token', 'The device code has expired');
+    }
+
+    // Clients polling faster than the interval are told to back off
+    const now = new Date();
+    if (record.lastPolledAt && now - record.lastPolledAt < record.interval * 1000) {
+      record.interval += SLOW_DOWN_INCREMENT_SECONDS;
+      record.lastPolledAt = now;
+      await record.save();
+      return oauthError('slow_down', `Poll at most every ${record.interval} seconds`);
+    }
+    record.lastPolledAt = now;
+
+    if (record.status === 'pending') {
+      await record.save();
+      return oauthError('authorization_pending', 'The user has not approved this device yet');
+    }
+    if (record.status === 'denied') {
+      await record.save();
+      return oauthError('access_denied', 'The user denied this device');
+    }
+    if (record.status === 'used') {
+      return oauthError('invalid_grant', 'The device code has already been used');
+    }
+
+    const user = await User.findById(record.userId);
+    if (!user) {
+      return oauthError('access_denied', 'User not found');
+    }
+
+    record.status = 'used';
+    await record.save();
+
+    return NextResponse.json({
+      access_token: signClientToken(user),
+      token_type: 'Bearer',
+      expires_in: CLIENT_TOKEN_TTL_SECONDS,
+    });
+  } catch (error) {
+    console.error('Error polling device token:', error);
+    return NextResponse.json({ error: 'server_error' }, { status: 500 });
+  }
+}
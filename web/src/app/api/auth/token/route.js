@@ -3,7 +3,7 @@ import { getServerSession } from 'next-auth';
 import { authOptions } from '@/app/api/auth/[...nextauth]/route';
 import connectDB from '@/../lib/mongodb';
 import User from '@/../models/User';
-import jwt from 'jsonwebtoken';
+import { signClientToken } from '@/../lib/clientToken';
 
 // Generate a token for the Rust client
 export async function POST(request) {
@@ -22,15 +22,7 @@ export async function POST(request) {
     }
 
     // Generate a long-lived token for the Rust client
-    const token = jwt.sign(
-      { 
-        userId: user._id, 
-        email: user.email,
-        type: 'rust-client'
-      },
-      process.env.JWT_SECRET,
-      { expiresIn: '30d' } // 30 days
-    );
+    const token = signClientToken(user);
 
     return NextResponse.json({ 
       token,
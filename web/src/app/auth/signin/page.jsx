@@ -13,6 +13,21 @@ function SignInContent() {
   
   const isFromDesktop = searchParams.get('source') === 'desktop';
 
+  // Desktop loopback sign-in: carry the client's port and state through to the token page
+  const desktopParams = new URLSearchParams({ source: 'desktop' });
+  ['redirect_port', 'state'].forEach((key) => {
+    const value = searchParams.get(key);
+    if (value) desktopParams.set(key, value);
+  });
+  const desktopTokenUrl = `/token?${desktopParams.toString()}`;
+
+  // Only follow same-site relative callbacks (e.g. back to /device)
+  const requestedCallback = searchParams.get('callbackUrl');
+  const callbackUrl =
+    requestedCallback && requestedCallback.startsWith('/') && !requestedCallback.startsWith('//')
+      ? requestedCallback
+      : '/dashboard';
+
   useEffect(() => {
     const setAuthProviders = async () => {
       const res = await getProviders();
@@ -25,12 +40,12 @@ function SignInContent() {
     if (status === 'authenticated') {
       // Redirect to token page if coming from desktop
       if (isFromDesktop) {
-        router.push('/token?source=desktop');
+        router.push(desktopTokenUrl);
       } else {
-        router.push('/dashboard');
+        router.push(callbackUrl);
       }
     }
-  }, [status, router, isFromDesktop]);
+  }, [status, router, isFromDesktop, desktopTokenUrl, callbackUrl]);
 
   return (
     <div className="min-h-screen bg-gradient-to-br from-blue-50 to-indigo-100 flex items-center justify-center">
@@ -55,7 +70,7 @@ function SignInContent() {
             Object.values(providers).map((provider) => (
               <button
                 key={provider.name}
-                onClick={() => signIn(provider.id, { callbackUrl: '/dashboard' })}
+                onClick={() => signIn(provider.id, { callbackUrl: isFromDesktop ? desktopTokenUrl : callbackUrl })}
                 className="w-full flex items-center justify-center px-4 py-3 border border-gray-300 rounded-md shadow-sm bg-white text-sm font-medium text-gray-700 hover:bg-gray-50 transition-colors"
               >
                 {provider.name === 'Google' && '🌐'}
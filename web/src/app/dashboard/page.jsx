@@ -12,7 +12,8 @@ export default function Dashboard() {
   const [metrics, setMetrics] = useState({
     totalEvents: 0,
     activeHours: 0,
-    appsUsed: 0
+    appsUsed: 0,
+    topDomains: []
   });
 
   useEffect(() => {
@@ -26,7 +27,7 @@ export default function Dashboard() {
 
   const calculateMetrics = (logsData) => {
     if (!logsData || logsData.length === 0) {
-      return { totalEvents: 0, activeHours: 0, appsUsed: 0 };
+      return { totalEvents: 0, activeHours: 0, appsUsed: 0, topDomains: [] };
     }
 
     // Calculate unique apps used
@@ -49,10 +50,23 @@ export default function Dashboard() {
       activeHours.add(hour);
     });
 
+    // Focused browser time per domain (attributed by the desktop client)
+    const domainSeconds = {};
+    logsData.forEach(log => {
+      if (log.type === 'browser' && log.data?.domain && log.data?.focusSeconds > 0) {
+        domainSeconds[log.data.domain] = (domainSeconds[log.data.domain] || 0) + log.data.focusSeconds;
+      }
+    });
+    const topDomains = Object.entries(domainSeconds)
+      .sort((a, b) => b[1] - a[1])
+      .slice(0, 5)
+      .map(([domain, seconds]) => ({ domain, seconds }));
+
     return {
       totalEvents: logsData.length,
       activeHours: activeHours.size,
-      appsUsed: uniqueApps.size
+      appsUsed: uniqueApps.size,
+      topDomains
     };
   };
 
@@ -127,6 +141,26 @@ export default function Dashboard() {
           </div>
         </div>
 
+        {metrics.topDomains.length > 0 && (
+          <div className="bg-white rounded-lg shadow mb-8">
+            <div className="px-6 py-4 border-b border-gray-200">
+              <h3 className="text-lg font-semibold text-gray-900">Time by Website</h3>
+            </div>
+            <div className="p-6 space-y-3">
+              {metrics.topDomains.map(({ domain, seconds }) => (
+                <div key={domain} className="flex justify-between items-center">
+                  <span className="text-gray-900">{domain}</span>
+                  <span className="text-sm text-gray-600">
+                    {seconds >= 3600
+                      ? `${Math.floor(seconds / 3600)}h ${Math.floor((seconds % 3600) / 60)}m`
+                      : `${Math.max(1, Math.round(seconds / 60))}m`}
+                  </span>
+                </div>
+              ))}
+            </div>
+          </div>
+        )}
+
         <div className="bg-white rounded-lg shadow">
           <div className="px-6 py-4 border-b border-gray-200">
             <h3 className="text-lg font-semibold text-gray-900">Recent Activity</h3>
@@ -157,8 +191,10 @@ export default function Dashboard() {
                       <div className="flex justify-between items-start">
                         <div>
                           <h4 className="font-medium text-gray-900">
-                            {log.type === 'window' && `${log.data.processName}: ${log.data.windowTitle}`}
-                            {log.type === 'browser' && `${log.data.browserTitle}`}
+                            {log.type === 'window' && (log.data.windowTitle
+                              ? `${log.data.processName}: ${log.data.windowTitle}`
+                              : `${log.data.processName} (${Math.round((log.data.durationSeconds || 0) / 60)}m)`)}
+                            {log.type === 'browser' && `${log.data.browserTitle || log.data.domain}`}
                             {log.type === 'keyboard' && 'Keyboard Activity'}
                             {log.type === 'mouse' && 'Mouse Activity'}
                           </h4>
@@ -0,0 +1,128 @@
+'use client';
+
+import { useSession } from 'next-auth/react';
+import { useRouter, useSearchParams } from 'next/navigation';
+import { useEffect, useState, Suspense } from 'react';
+
+function DevicePageContent() {
+  const { data: session, status } = useSession();
+  const router = useRouter();
+  const searchParams = useSearchParams();
+  const [userCode, setUserCode] = useState(searchParams.get('user_code') || '');
+  const [loading, setLoading] = useState(false);
+  const [result, setResult] = useState(null);
+  const [error, setError] = useState('');
+
+  useEffect(() => {
+    if (status === 'unauthenticated') {
+      const callbackUrl = `/device${userCode ? `?user_code=${encodeURIComponent(userCode)}` : ''}`;
+      router.push(`/auth/signin?callbackUrl=${encodeURIComponent(callbackUrl)}`);
+    }
+  }, [status, router, userCode]);
+
+  const submit = async (action) => {
+    setLoading(true);
+    setError('');
+    try {
+      const res = await fetch('/api/auth/device/approve', {
+        method: 'POST',
+        headers: { 'Content-Type': 'application/json' },
+        body: JSON.stringify({ userCode, action }),
+      });
+      const data = await res.json();
+      if (res.ok) {
+        setResult(data.status);
+      } else {
+        setError(data.error || 'Something went wrong');
+      }
+    } catch (err) {
+      console.error('Error approving device:', err);
+      setError('Something went wrong');
+    } finally {
+      setLoading(false);
+    }
+  };
+
+  if (status === 'loading') {
+    return (
+      <div className="min-h-screen flex items-center justify-center">
+        <div className="text-lg">Loading...</div>
+      </div>
+    );
+  }
+
+  if (!session) {
+    return null;
+  }
+
+  return (
+    <div className="min-h-screen bg-gradient-to-br from-blue-50 to-indigo-100 flex items-center justify-center">
+      <div className="bg-white p-8 rounded-lg shadow-md w-full max-w-md">
+        <div className="text-center mb-8">
+          <div className="text-6xl mb-4">🖥️</div>
+          <h1 className="text-3xl font-bold text-gray-900 mb-2">Connect a Device</h1>
+          <p className="text-gray-600">Enter the code shown by <code>chronos login --device</code></p>
+        </div>
+
+        {result === 'approved' ? (
+          <div className="p-4 bg-green-50 rounded-lg text-green-800 text-center">
+            Device connected. It will start syncing in a few seconds.
+          </div>
+        ) : result === 'denied' ? (
+          <div className="p-4 bg-gray-50 rounded-lg text-gray-800 text-center">
+            Request denied. The device was not connected.
+          </div>
+        ) : (
+          <>
+            <input
+              value={userCode}
+              onChange={(e) => setUserCode(e.target.value)}
+              placeholder="XXXX-XXXX"
+              className="w-full p-3 border border-gray-300 rounded-lg text-center text-2xl font-mono tracking-widest uppercase mb-4"
+            />
+            {error && <p className="text-sm text-red-600 mb-4">{error}</p>}
+            <div className="flex space-x-4">
+              <button
+                onClick={() => submit('approve')}
+                disabled={loading || !userCode}
+                className="flex-1 bg-blue-600 hover:bg-blue-700 disabled:bg-blue-400 text-white px-6 py-3 rounded-lg transition-colors"
+              >
+                {loading ? 'Connecting...' : 'Connect'}
+              </button>
+              <button
+                onClick={() => submit('deny')}
+                disabled={loading || !userCode}
+                className="bg-gray-200 hover:bg-gray-300 disabled:bg-gray-100 text-gray-800 px-6 py-3 rounded-lg transition-colors"
+              >
+                Deny
+              </button>
+            </div>
+            <p className="text-sm text-gray-500 mt-4">
+              Signed in as {session.user.email}. Only connect devices you started the login from.
+            </p>
+          </>
+        )}
+      </div>
+    </div>
+  );
+}
+
+function LoadingSpinner() {
+  return (
+    <div className="min-h-screen bg-gradient-to-br from-blue-50 to-indigo-100 flex items-center justify-center">
+      <div className="bg-white p-8 rounded-lg shadow-md w-full max-w-md text-center">
+        <div className="text-6xl mb-4">⏳</div>
+        <h1 className="text-2xl font-bold text-gray-900 mb-2">Loading...</h1>
+        <p className="text-gray-600">Setting up device page</p>
+      </div>
+    </div>
+  );
+}
+
+export default function DevicePage() {
+  return (
+    <Suspense fallback={<LoadingSpinner />}>
+      <DevicePageContent />
+    </Suspense>
+  );
+}
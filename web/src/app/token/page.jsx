@@ -14,6 +14,13 @@ function TokenPageContent() {
   
   const isFromDesktop = searchParams.get('source') === 'desktop';
 
+  // Loopback sign-in: the desktop app listens on 127.0.0.1:<redirect_port>
+  const redirectPort = Number.parseInt(searchParams.get('redirect_port') || '', 10);
+  const desktopState = searchParams.get('state');
+  const canReturnToDesktop =
+    isFromDesktop && desktopState && Number.isInteger(redirectPort) && redirectPort >= 1024 && redirectPort <= 65535;
+  const [returnedToDesktop, setReturnedToDesktop] = useState(false);
+
   useEffect(() => {
     if (status === 'unauthenticated') {
       router.push('/auth/signin');
@@ -46,6 +53,15 @@ function TokenPageContent() {
     }
   };
 
+  // Hand the token straight back to the desktop app once we have it
+  useEffect(() => {
+    if (token && canReturnToDesktop && !returnedToDesktop) {
+      setReturnedToDesktop(true);
+      const params = new URLSearchParams({ token, state: desktopState });
+      window.location.href = `http://127.0.0.1:${redirectPort}/callback?${params.toString()}`;
+    }
+  }, [token, canReturnToDesktop, returnedToDesktop, desktopState, redirectPort]);
+
   const copyToken = () => {
     navigator.clipboard.writeText(token);
     setCopied(true);
@@ -97,7 +113,9 @@ function TokenPageContent() {
                     Desktop Setup Complete!
                   </h2>
                   <p className="text-gray-600">
-                    Copy this token and paste it in your desktop app to finish setup.
+                    {canReturnToDesktop
+                      ? 'Sending your token to the desktop app...'
+                      : 'Copy this token and paste it in your desktop app to finish setup.'}
                   </p>
                 </div>
               </>